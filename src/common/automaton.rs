@@ -0,0 +1,165 @@
+use crate::common::Grid;
+use crate::common::Point;
+
+// A dense grid of cells that all change at once, each based on its own value and how many of each
+// value are in the eight cells around it. Two grids are kept so stepping doesn't allocate.
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Boundary {
+    Fixed, // Cells past the edge don't exist, so edge cells have fewer neighbours
+    Wrap,  // Each edge joins to the opposite one
+}
+
+pub struct Neighbourhood<T> {
+    cells: [Option<T>; 8],
+}
+
+impl<T: Copy + Eq> Neighbourhood<T> {
+    pub fn count(&self, value: T) -> usize {
+        self.cells.iter().filter(|&&c| c == Some(value)).count()
+    }
+
+    pub fn len(&self) -> usize {
+        // Fewer than eight at a fixed edge
+        self.cells.iter().filter(|c| c.is_some()).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+fn neighbourhood<T: Copy>(
+    cells: &Grid<T>,
+    p: Point,
+    (min, max): (Point, Point),
+    boundary: Boundary,
+) -> Neighbourhood<T> {
+    let (width, height) = (max.x - min.x + 1, max.y - min.y + 1);
+    let mut neighbours = [None; 8];
+    for (neighbour, n) in neighbours.iter_mut().zip(p.adjacents()) {
+        let n = match boundary {
+            Boundary::Fixed => n,
+            Boundary::Wrap => Point {
+                x: min.x + (n.x - min.x).rem_euclid(width),
+                y: min.y + (n.y - min.y).rem_euclid(height),
+            },
+        };
+        *neighbour = cells.get(n).copied();
+    }
+    Neighbourhood { cells: neighbours }
+}
+
+#[derive(Clone)]
+pub struct Automaton<T, R> {
+    cells: Grid<T>,
+    next: Grid<T>,
+    bounds: (Point, Point),
+    boundary: Boundary,
+    rule: R,
+}
+
+impl<T, R> Automaton<T, R>
+where
+    T: Copy + Eq,
+    R: Fn(T, &Neighbourhood<T>) -> T,
+{
+    pub fn new(cells: Grid<T>, boundary: Boundary, rule: R) -> Self {
+        assert!(cells.is_dense() == true, "Automaton needs a dense grid");
+        let bounds = cells.bounds().expect("Automaton needs at least one cell");
+        Self {
+            next: cells.clone(),
+            cells,
+            bounds,
+            boundary,
+            rule,
+        }
+    }
+
+    pub fn cells(&self) -> &Grid<T> {
+        &self.cells
+    }
+
+    pub fn count(&self, value: T) -> usize {
+        self.cells.values().filter(|&&c| c == value).count()
+    }
+
+    pub fn step(&mut self) {
+        for (p, &cell) in self.cells.iter() {
+            let neighbours = neighbourhood(&self.cells, p, self.bounds, self.boundary);
+            *self.next.get_mut(p).unwrap() = (self.rule)(cell, &neighbours);
+        }
+        std::mem::swap(&mut self.cells, &mut self.next);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn life(cell: char, neighbours: &Neighbourhood<char>) -> char {
+        match (cell, neighbours.count('#')) {
+            ('#', 2) | (_, 3) => '#',
+            _ => '.',
+        }
+    }
+
+    #[test]
+    fn test_fixed() {
+        // A blinker flips between across and down
+        let input = "
+.....
+..#..
+..#..
+..#..
+.....";
+        let mut automaton = Automaton::new(Grid::from_string(input), Boundary::Fixed, life);
+        automaton.step();
+        assert_eq!(
+            automaton.cells().to_string().trim(),
+            "
+.....
+.....
+.###.
+.....
+....."
+                .trim()
+        );
+        automaton.step();
+        assert_eq!(automaton.cells().to_string().trim(), input.trim());
+
+        // The corner only has three neighbours
+        let corner = neighbourhood(
+            automaton.cells(),
+            Point::new(),
+            automaton.bounds,
+            Boundary::Fixed,
+        );
+        assert_eq!(corner.len(), 3);
+    }
+
+    #[test]
+    fn test_wrap() {
+        // A glider crosses the edges and is back where it started after moving one place down and
+        // right for every cell across
+        let input = "
+.#....
+..#...
+###...
+......
+......
+......";
+        let grid = Grid::from_string(input);
+        let mut fixed = Automaton::new(grid.clone(), Boundary::Fixed, life);
+        let mut wrap = Automaton::new(grid, Boundary::Wrap, life);
+        for _ in 0..24 {
+            fixed.step();
+            wrap.step();
+        }
+        assert_eq!(wrap.cells().to_string().trim(), input.trim());
+        assert_eq!(wrap.count('#'), 5);
+
+        // Without wrapping it hits the corner and turns into a block
+        assert_eq!(fixed.count('#'), 4);
+    }
+}
@@ -0,0 +1,161 @@
+use std::fmt;
+
+// A row of cells that live or die based on the two cells either side of them, as in day 12. Cells
+// are packed 64 to a word, with the leftmost cell in the lowest bit, and a whole word is worked out
+// at once by matching the shifted words against each rule that makes a live cell.
+
+const BITS: i64 = 64;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BitAutomaton {
+    live_rules: Vec<usize>, // Patterns that give a live cell, the leftmost cell being the top bit
+    words: Vec<u64>,
+    first: i64, // Index of the cell in the lowest bit of the first word
+}
+
+impl BitAutomaton {
+    pub fn new(cells: &[bool], first: i64, rules: &[bool]) -> Self {
+        // Rules are indexed by pattern, so there are 32. An empty pattern can't make a live cell, or
+        // the row would fill up to infinity.
+        assert_eq!(rules.len(), 32, "Need a rule for every pattern");
+        assert!(rules[0] == false, "Empty cells can't come alive");
+
+        let mut words = vec![0; cells.len().div_ceil(BITS as usize)];
+        for (i, _) in cells.iter().enumerate().filter(|&(_, &cell)| cell == true) {
+            words[i / BITS as usize] |= 1 << (i % BITS as usize);
+        }
+        let mut automaton = Self {
+            live_rules: (0..32).filter(|&r| rules[r] == true).collect(),
+            words,
+            first,
+        };
+        automaton.trim();
+        automaton
+    }
+
+    fn trim(&mut self) {
+        // Drop empty words from each end, so a pattern that moves along doesn't leave a trail
+        let leading = self.words.iter().take_while(|&&w| w == 0).count();
+        self.words.drain(..leading);
+        self.first += leading as i64 * BITS;
+        while self.words.last() == Some(&0) {
+            self.words.pop();
+        }
+    }
+
+    pub fn step(&mut self) {
+        // New cells can appear up to two beyond the current ends, so make room for them
+        if self.words.first().is_some_and(|&w| w & 0b11 != 0) {
+            self.words.insert(0, 0);
+            self.first -= BITS;
+        }
+        if self.words.last().is_some_and(|&w| w >> (BITS - 2) != 0) {
+            self.words.push(0);
+        }
+
+        let word = |i: usize| self.words.get(i).copied().unwrap_or(0);
+        let next: Vec<u64> = (0..self.words.len())
+            .map(|i| {
+                let (prev, curr, next) = (i.checked_sub(1).map_or(0, word), word(i), word(i + 1));
+
+                // Bit n of each of these is the cell at that distance from cell n, leftmost first
+                let neighbourhood = [
+                    (curr << 2) | (prev >> (BITS - 2)),
+                    (curr << 1) | (prev >> (BITS - 1)),
+                    curr,
+                    (curr >> 1) | (next << (BITS - 1)),
+                    (curr >> 2) | (next << (BITS - 2)),
+                ];
+                self.live_rules
+                    .iter()
+                    .map(|&rule| {
+                        neighbourhood
+                            .iter()
+                            .enumerate()
+                            .fold(u64::MAX, |matches, (n, &cells)| {
+                                if rule & (0b10000 >> n) != 0 {
+                                    matches & cells
+                                } else {
+                                    matches & !cells
+                                }
+                            })
+                    })
+                    .fold(0, |live, matches| live | matches)
+            })
+            .collect();
+
+        self.words = next;
+        self.trim();
+    }
+
+    pub fn live_cells(&self) -> impl Iterator<Item = i64> + '_ {
+        self.words.iter().enumerate().flat_map(move |(i, &w)| {
+            let base = self.first + i as i64 * BITS;
+            (0..BITS)
+                .filter(move |&bit| w & (1 << bit) != 0)
+                .map(move |bit| base + bit)
+        })
+    }
+
+    pub fn count(&self) -> u32 {
+        self.words.iter().map(|w| w.count_ones()).sum()
+    }
+
+    pub fn sum(&self) -> i64 {
+        self.live_cells().sum()
+    }
+}
+
+impl fmt::Display for BitAutomaton {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // From the first live cell to the last
+        let cells: Vec<i64> = self.live_cells().collect();
+        if let (Some(&first), Some(&last)) = (cells.first(), cells.last()) {
+            let mut live = cells.iter().peekable();
+            for i in first..=last {
+                if live.next_if_eq(&&i).is_some() {
+                    write!(f, "#")?;
+                } else {
+                    write!(f, ".")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_step() {
+        // A cell is live if it or the cell to its right was, so a row grows one to the left every step
+        let rules: Vec<bool> = (0..32).map(|r| r & 0b00110 != 0).collect();
+        let mut automaton = BitAutomaton::new(&[true], 0, &rules);
+        assert_eq!(automaton.to_string(), "#");
+
+        // Crosses the word boundary to the left and keeps going
+        for _ in 0..100 {
+            automaton.step();
+        }
+        assert_eq!(automaton.to_string(), "#".repeat(101));
+        assert_eq!(automaton.live_cells().next(), Some(-100));
+        assert_eq!(automaton.count(), 101);
+        assert_eq!(automaton.sum(), (-100..=0).sum());
+    }
+
+    #[test]
+    fn test_glide() {
+        // .#... => # moves a cell one to the right each step, leaving empty words behind
+        let rules: Vec<bool> = (0..32).map(|r| r == 0b01000).collect();
+        let cells = [false, true, false, false, false, true];
+        let mut automaton = BitAutomaton::new(&cells, 60, &rules);
+        for _ in 0..200 {
+            automaton.step();
+        }
+        assert_eq!(automaton.to_string(), "#...#");
+        assert_eq!(automaton.live_cells().collect::<Vec<i64>>(), vec![261, 265]);
+        assert_eq!(automaton.words.len(), 1);
+    }
+}
@@ -0,0 +1,916 @@
+use crate::common::Grid;
+use crate::common::Palette;
+use crate::common::Point;
+use crate::common::Rgb;
+use crate::common::Simulation;
+use nom::{
+    branch::alt,
+    bytes::complete::tag,
+    character::complete::{alpha1, anychar, char, digit1, line_ending, multispace0, space1},
+    combinator::{map, map_res, opt},
+    multi::{separated_list0, separated_list1},
+    sequence::{delimited, preceded, separated_pair, tuple},
+    IResult,
+};
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::Write;
+
+// A battle in the caves, as in day 15. The units come from a legend that gives each map character a
+// faction and stats, so the goblins and elves are just the default setup and any number of factions
+// can fight. Units attack anything not in their own faction. Every turn is logged, and the log can
+// be written out a line per turn and replayed to see the board after any round.
+
+const DEFAULT_LEGEND: &str = "
+G Goblin hp=200 attack=3
+E Elf hp=200 attack=3";
+
+const FACTION_COLOURS: [Rgb; 4] = [[40, 160, 40], [60, 100, 220], [200, 60, 60], [200, 160, 40]];
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UnitType {
+    pub symbol: char,
+    pub faction: String,
+    pub hit_points: u32,
+    pub attack: u32,
+    pub range: u32, // How many steps away it can attack from, one for only next to it
+}
+
+impl UnitType {
+    fn parser(input: &str) -> IResult<&str, Self> {
+        // e.g. "A Archer hp=100 attack=5 range=3", where the range is optional
+        let number = |input| map_res(digit1, |x: &str| x.parse::<u32>())(input);
+        let (input, (symbol, faction, hit_points, attack, range)) = tuple((
+            preceded(multispace0, anychar),
+            preceded(space1, map(alpha1, |x: &str| x.to_owned())),
+            preceded(tag(" hp="), number),
+            preceded(tag(" attack="), number),
+            opt(preceded(tag(" range="), number)),
+        ))(input)?;
+
+        Ok((
+            input,
+            Self {
+                symbol,
+                faction,
+                hit_points,
+                attack,
+                range: range.unwrap_or(1),
+            },
+        ))
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Legend {
+    unit_types: Vec<UnitType>,
+}
+
+impl Legend {
+    pub fn from_string(input: &str) -> Self {
+        let (_input, unit_types) = delimited(
+            multispace0,
+            separated_list1(line_ending, UnitType::parser),
+            multispace0,
+        )(input)
+        .unwrap();
+        Self { unit_types }
+    }
+
+    pub fn unit_types(&self) -> &[UnitType] {
+        &self.unit_types
+    }
+
+    fn kind(&self, symbol: char) -> Option<usize> {
+        self.unit_types.iter().position(|t| t.symbol == symbol)
+    }
+
+    fn factions(&self) -> Vec<&str> {
+        // In the order they first appear
+        let mut factions: Vec<&str> = Vec::new();
+        for unit_type in self.unit_types.iter() {
+            if factions.contains(&unit_type.faction.as_str()) == false {
+                factions.push(&unit_type.faction);
+            }
+        }
+        factions
+    }
+}
+
+impl Default for Legend {
+    fn default() -> Self {
+        Self::from_string(DEFAULT_LEGEND)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Tile {
+    Empty,
+    Wall,
+    Unit {
+        kind: usize,    // Place in the legend's unit types
+        faction: usize, // Place in the legend's factions
+        hp: u32,
+    },
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UnitTurn {
+    pub round: u32,
+    pub symbol: char,
+    pub start: Point,
+    pub targets: Vec<Point>, // The nearest spaces in range of an enemy, tied
+    pub steps: Vec<Point>,   // The first steps of the shortest paths to the chosen target, tied
+    pub path: Vec<Point>,    // The shortest path to the chosen target, first in reading order
+    pub moved_to: Option<Point>,
+    pub attacked: Option<(Point, u32)>, // The enemy hit and the damage dealt
+}
+
+impl UnitTurn {
+    fn parser(input: &str) -> IResult<&str, Self> {
+        // e.g. "3 G 4,2 targets=5,3;6,2 steps=4,3;5,2 path=4,3;5,3 move=4,3 hit=-"
+        let number = |input| map_res(digit1, |x: &str| x.parse::<u32>())(input);
+        let points = |input| separated_list0(char(';'), Point::parser)(input);
+        let point_or_none =
+            |input| alt((map(char('-'), |_| None), map(Point::parser, Some)))(input);
+        let (input, (round, symbol, start, targets, steps, path, moved_to, attacked)) =
+            tuple((
+                preceded(multispace0, number),
+                preceded(space1, anychar),
+                preceded(space1, Point::parser),
+                preceded(tag(" targets="), points),
+                preceded(tag(" steps="), points),
+                preceded(tag(" path="), points),
+                preceded(tag(" move="), point_or_none),
+                preceded(
+                    tag(" hit="),
+                    alt((
+                        map(char('-'), |_| None),
+                        map(separated_pair(Point::parser, char(':'), number), Some),
+                    )),
+                ),
+            ))(input)?;
+
+        Ok((
+            input,
+            Self {
+                round,
+                symbol,
+                start,
+                targets,
+                steps,
+                path,
+                moved_to,
+                attacked,
+            },
+        ))
+    }
+}
+
+impl fmt::Display for UnitTurn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let point_or_none = |point: Option<Point>| match point {
+            Some(p) => format!("{},{}", p.x, p.y),
+            None => "-".to_string(),
+        };
+        let points = |points: &[Point]| -> String {
+            let points: Vec<String> = points.iter().map(|&p| point_or_none(Some(p))).collect();
+            points.join(";")
+        };
+        write!(
+            f,
+            "{} {} {},{} targets={} steps={} path={} move={}",
+            self.round,
+            self.symbol,
+            self.start.x,
+            self.start.y,
+            points(&self.targets),
+            points(&self.steps),
+            points(&self.path),
+            point_or_none(self.moved_to)
+        )?;
+        match self.attacked {
+            Some((p, damage)) => write!(f, " hit={},{}:{}", p.x, p.y, damage),
+            None => write!(f, " hit=-"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct CombatLog {
+    turns: Vec<UnitTurn>,
+}
+
+impl CombatLog {
+    pub fn from_string(input: &str) -> Self {
+        // One turn per line, as written by to_string
+        let turns = input
+            .lines()
+            .filter(|line| line.trim().is_empty() == false)
+            .map(|line| match UnitTurn::parser(line) {
+                Ok(("", turn)) => turn,
+                _ => panic!("Bad combat log line: {}", line),
+            })
+            .collect();
+        Self { turns }
+    }
+
+    pub fn turns(&self) -> &[UnitTurn] {
+        &self.turns
+    }
+
+    pub fn round(&self, round: u32) -> impl Iterator<Item = &UnitTurn> {
+        self.turns.iter().filter(move |t| t.round == round)
+    }
+}
+
+impl fmt::Display for CombatLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for turn in self.turns.iter() {
+            writeln!(f, "{}", turn)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum RoundEnd {
+    Complete,
+    NoEnemies, // A unit had no enemies left, so the round ended early
+    Lost,      // A unit of the watched faction died
+}
+
+#[derive(Clone)]
+struct DistanceField {
+    // For each space, how many steps it is to the nearest spaces in range of an enemy and which
+    // spaces those are, in reading order
+    nearest: Grid<Option<(u32, Vec<Point>)>>,
+}
+
+impl DistanceField {
+    fn get(&self, p: Point) -> Option<(u32, &[Point])> {
+        match self.nearest.get(p) {
+            Some(Some((distance, targets))) => Some((*distance, targets)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct BattleMap {
+    tiles: Grid<Tile>,
+    legend: Legend,
+    round: u32, // The round being played, or last played
+    log: CombatLog,
+    fields: HashMap<(usize, u32), DistanceField>, // By faction and range
+}
+
+impl BattleMap {
+    pub fn from_string(input: &str) -> Self {
+        Self::with_legend(input, Legend::default())
+    }
+
+    pub fn with_legend(input: &str, legend: Legend) -> Self {
+        let factions = legend.factions();
+        let tiles = Grid::<char>::from_string(input).map(|p, &c| match c {
+            '.' => Tile::Empty,
+            '#' => Tile::Wall,
+            _ => match legend.kind(c) {
+                Some(kind) => Tile::Unit {
+                    kind,
+                    faction: factions
+                        .iter()
+                        .position(|&f| f == legend.unit_types[kind].faction)
+                        .unwrap(),
+                    hp: legend.unit_types[kind].hit_points,
+                },
+                None => panic!("Unknown tile at {}: {}", p, c),
+            },
+        });
+        Self {
+            tiles,
+            legend,
+            round: 0,
+            log: CombatLog::default(),
+            fields: HashMap::new(),
+        }
+    }
+
+    pub fn set_attack(&mut self, faction: &str, attack: u32) {
+        for unit_type in self.legend.unit_types.iter_mut() {
+            if unit_type.faction == faction {
+                unit_type.attack = attack;
+            }
+        }
+    }
+
+    pub fn round(&self) -> u32 {
+        self.round
+    }
+
+    pub fn log(&self) -> &CombatLog {
+        &self.log
+    }
+
+    fn unit_type(&self, kind: usize) -> &UnitType {
+        &self.legend.unit_types[kind]
+    }
+
+    fn faction(&self, p: Point) -> Option<usize> {
+        match self.tiles.get(p) {
+            Some(&Tile::Unit { faction, .. }) => Some(faction),
+            _ => None,
+        }
+    }
+
+    fn is_enemy(&self, p: Point, faction: usize) -> bool {
+        self.faction(p).is_some_and(|f| f != faction)
+    }
+
+    pub fn to_string(&self, with_details: bool) -> String {
+        let to_char = |tile: &Tile| match *tile {
+            Tile::Empty => '.',
+            Tile::Wall => '#',
+            Tile::Unit { kind, .. } => self.unit_type(kind).symbol,
+        };
+        let map = self.tiles.render(|_p, tile| tile.map_or('.', to_char));
+        if with_details == false {
+            return map;
+        }
+
+        // List the units on each row after it
+        let mut output = String::new();
+        for (y, line) in map.lines().enumerate() {
+            let unit_strings: Vec<String> = self
+                .tiles
+                .iter()
+                .filter_map(|(p, tile)| match *tile {
+                    Tile::Unit { hp, .. } if p.y == y as i32 => {
+                        Some(format!("{}({})", to_char(tile), hp))
+                    }
+                    _ => None,
+                })
+                .collect();
+            if unit_strings.is_empty() == true {
+                writeln!(output, "{}", line).unwrap();
+            } else {
+                writeln!(output, "{}   {}", line, unit_strings.join(", ")).unwrap();
+            }
+        }
+        output
+    }
+
+    pub fn palette(&self) -> Palette {
+        // Each faction has its own colour
+        let palette = Palette::new([0, 0, 0])
+            .with('.', [200, 190, 160])
+            .with('#', [80, 70, 60]);
+        let factions = self.legend.factions();
+        self.legend.unit_types.iter().fold(palette, |palette, t| {
+            let i = factions.iter().position(|&f| f == t.faction).unwrap();
+            palette.with(t.symbol, FACTION_COLOURS[i % FACTION_COLOURS.len()])
+        })
+    }
+
+    // Returns in reading order
+    fn identify_units(&self) -> Vec<Point> {
+        let mut units: Vec<Point> = self
+            .tiles
+            .iter()
+            .filter(|&(_point, tile)| matches!(tile, Tile::Unit { .. }))
+            .map(|(point, _tile)| point)
+            .collect();
+        units.sort_by(Point::cmp_y_x);
+        units
+    }
+
+    fn identify_enemies(&self, faction: usize) -> Vec<Point> {
+        self.tiles
+            .points()
+            .filter(|&point| self.is_enemy(point, faction))
+            .collect()
+    }
+
+    fn identify_adjacent_empty(&self, unit: &Point) -> Vec<Point> {
+        self.tiles
+            .orthogonals(*unit)
+            .filter(|&(_point, tile)| tile == &Tile::Empty)
+            .map(|(point, _tile)| point)
+            .collect()
+    }
+
+    fn within_range(p: Point, range: u32) -> impl Iterator<Item = Point> {
+        // Every point within that many steps, not counting walls in the way. Some may be off the map.
+        let range = range as i32;
+        (-range..=range)
+            .flat_map(move |dy| {
+                let reach = range - dy.abs();
+                (-reach..=reach).map(move |dx| Point {
+                    x: p.x + dx,
+                    y: p.y + dy,
+                })
+            })
+            .filter(move |&q| q != p)
+    }
+
+    fn identify_enemies_in_range(&self, unit: &Point, faction: usize, range: u32) -> Vec<Point> {
+        Self::within_range(*unit, range)
+            .filter(|&point| self.is_enemy(point, faction))
+            .collect()
+    }
+
+    pub fn play_round(&mut self) -> bool {
+        // Gives false if the round ended early because a unit had no enemies left
+        self.play_turns(None) == RoundEnd::Complete
+    }
+
+    fn play_turns(&mut self, watched: Option<usize>) -> RoundEnd {
+        // Stops straight after any turn that kills a unit of the watched faction
+        self.round += 1;
+        let watched_count = watched.map(|faction| self.count_faction(faction));
+        let units = self.identify_units();
+        for unit in units {
+            if matches!(self.tiles.get(unit), Some(Tile::Unit { .. })) == false {
+                // Unit was killed in an earlier iteration
+                continue;
+            }
+            let turn = match self.take_turn(unit) {
+                Some(turn) => turn,
+                None => return RoundEnd::NoEnemies, // No more enemies, end immediately
+            };
+            let killed = match turn.attacked {
+                Some((enemy, _damage)) => self.tiles.get(enemy) == Some(&Tile::Empty),
+                None => false,
+            };
+            self.log.turns.push(turn);
+            if killed == true && watched.map(|faction| self.count_faction(faction)) != watched_count
+            {
+                return RoundEnd::Lost;
+            }
+        }
+
+        RoundEnd::Complete
+    }
+
+    fn take_turn(&mut self, unit: Point) -> Option<UnitTurn> {
+        // Gives None if there are no enemies left to fight
+        let (kind, faction) = match self.tiles.get(unit) {
+            Some(&Tile::Unit { kind, faction, .. }) => (kind, faction),
+            _ => panic!("No unit at {}", unit),
+        };
+        let unit_type = self.unit_type(kind);
+        let (range, attack) = (unit_type.range, unit_type.attack);
+        let mut turn = UnitTurn {
+            round: self.round,
+            symbol: unit_type.symbol,
+            start: unit,
+            targets: Vec::new(),
+            steps: Vec::new(),
+            path: Vec::new(),
+            moved_to: None,
+            attacked: None,
+        };
+
+        // If there are no enemies in range, move towards one
+        let mut unit_location = unit;
+        let enemies_in_range = self.identify_enemies_in_range(&unit_location, faction, range);
+        if enemies_in_range.is_empty() == true {
+            let enemies = self.identify_enemies(faction);
+            if enemies.is_empty() == true {
+                return None;
+            }
+            if let Some(step) = self.choose_step(unit, faction, range, &mut turn) {
+                unit_location = step;
+                turn.moved_to = Some(step);
+                self.step(unit, step);
+            }
+        }
+
+        // Check again if there are enemies in range (we may have moved). Attack if possible.
+        let hp = |point: &Point| match self.tiles.get(*point) {
+            Some(&Tile::Unit { hp, .. }) => hp,
+            _ => panic!("Unexpected tile"),
+        };
+        let mut enemies_can_attack: Vec<Point> =
+            self.identify_enemies_in_range(&unit_location, faction, range);
+        enemies_can_attack.sort_by(|a, b| hp(a).cmp(&hp(b)).then(Point::cmp_y_x(a, b)));
+
+        // Attack the enemy with the lowest HP, first in reading order if there's a tie
+        if let Some(&enemy) = enemies_can_attack.first() {
+            turn.attacked = Some((enemy, attack));
+            self.hit(enemy, attack);
+        }
+        Some(turn)
+    }
+
+    fn distance_field(&self, faction: usize, range: u32) -> DistanceField {
+        // Spreads out from every space in range of an enemy at once, one step at a time. A space
+        // reached at the same distance from more than one target keeps all of them.
+        let (min, max) = self.tiles.bounds().unwrap();
+        let mut nearest = Grid::dense(min, max, None);
+        let mut level: Vec<Point> = self
+            .identify_enemies(faction)
+            .into_iter()
+            .flat_map(|enemy| Self::within_range(enemy, range))
+            .filter(|&point| self.tiles.get(point) == Some(&Tile::Empty))
+            .collect();
+        level.sort_by(Point::cmp_y_x);
+        level.dedup();
+        for &target in level.iter() {
+            nearest.insert(target, Some((0, vec![target])));
+        }
+
+        let mut distance = 0;
+        while level.is_empty() == false {
+            distance += 1;
+            let mut next_level = Vec::new();
+            for point in level {
+                let (_distance, targets) = nearest.get(point).unwrap().clone().unwrap();
+                for next in self.identify_adjacent_empty(&point) {
+                    match nearest.get_mut(next) {
+                        Some(entry @ None) => {
+                            *entry = Some((distance, targets.clone()));
+                            next_level.push(next);
+                        }
+                        Some(Some((d, t))) if *d == distance && targets != *t => {
+                            t.extend(targets.iter().copied());
+                            t.sort_by(Point::cmp_y_x);
+                            t.dedup();
+                        }
+                        _ => (),
+                    }
+                }
+            }
+            level = next_level;
+        }
+        DistanceField { nearest }
+    }
+
+    fn choose_step(
+        &mut self,
+        unit: Point,
+        faction: usize,
+        range: u32,
+        turn: &mut UnitTurn,
+    ) -> Option<Point> {
+        // Every unit in a faction with the same range heads for the same spaces, so they share one
+        // distance field until something moves or dies
+        if self.fields.contains_key(&(faction, range)) == false {
+            let field = self.distance_field(faction, range);
+            self.fields.insert((faction, range), field);
+        }
+        let field = &self.fields[&(faction, range)];
+
+        // Find the closest reachable spaces, and take the first in reading order if there's a tie
+        let neighbours = self.identify_adjacent_empty(&unit);
+        let distance = neighbours
+            .iter()
+            .filter_map(|&point| field.get(point))
+            .map(|(distance, _targets)| distance)
+            .min()?; // None if there are no paths to a target
+        let closer: Vec<(Point, &[Point])> = neighbours
+            .iter()
+            .filter_map(|&point| match field.get(point) {
+                Some((d, targets)) if d == distance => Some((point, targets)),
+                _ => None,
+            })
+            .collect();
+        let mut targets: Vec<Point> = closer
+            .iter()
+            .flat_map(|(_point, targets)| targets.iter().copied())
+            .collect();
+        targets.sort_by(Point::cmp_y_x);
+        targets.dedup();
+        let target = targets[0];
+        turn.targets = targets;
+
+        // Of the steps that start a shortest path there, take the first in reading order
+        let mut steps: Vec<Point> = closer
+            .iter()
+            .filter(|(_point, targets)| targets.contains(&target) == true)
+            .map(|&(point, _targets)| point)
+            .collect();
+        steps.sort_by(Point::cmp_y_x);
+
+        // Follow the field down from the first step to the target, first in reading order
+        let mut path = vec![steps[0]];
+        for d in (0..distance).rev() {
+            let mut next: Vec<Point> = self
+                .identify_adjacent_empty(path.last().unwrap())
+                .into_iter()
+                .filter(|&point| match field.get(point) {
+                    Some((distance, targets)) => distance == d && targets.contains(&target),
+                    None => false,
+                })
+                .collect();
+            next.sort_by(Point::cmp_y_x);
+            path.push(next[0]);
+        }
+        turn.steps = steps;
+        turn.path = path;
+        turn.steps.first().copied()
+    }
+
+    fn step(&mut self, from: Point, to: Point) {
+        // Step by overwriting current space and new space
+        self.fields.clear();
+        let unit_data = self.tiles.insert(from, Tile::Empty).unwrap();
+        let previous = self.tiles.insert(to, unit_data).unwrap();
+        assert_eq!(previous, Tile::Empty);
+    }
+
+    fn hit(&mut self, enemy: Point, damage: u32) {
+        let enemy_adjusted = match self.tiles.get(enemy) {
+            Some(&Tile::Unit { hp, .. }) if hp <= damage => {
+                // Dead, which opens up a space
+                self.fields.clear();
+                Tile::Empty
+            }
+            Some(&Tile::Unit { kind, faction, hp }) => Tile::Unit {
+                kind,
+                faction,
+                hp: hp - damage,
+            },
+            _ => panic!("Unexpected tile"),
+        };
+        self.tiles.insert(enemy, enemy_adjusted);
+    }
+
+    pub fn replay(&self, log: &CombatLog, rounds: u32) -> Self {
+        // Rebuilds the board after that many rounds from the log of a battle that started from this
+        // one. Nothing is decided again, the moves and hits are just done as they were logged.
+        let mut battle_map = self.clone();
+        for turn in log.turns.iter().take_while(|t| t.round <= rounds) {
+            let symbol = match battle_map.tiles.get(turn.start) {
+                Some(&Tile::Unit { kind, .. }) => Some(battle_map.unit_type(kind).symbol),
+                _ => None,
+            };
+            assert_eq!(symbol, Some(turn.symbol), "Log doesn't match the board");
+            if let Some(to) = turn.moved_to {
+                battle_map.step(turn.start, to);
+            }
+            if let Some((enemy, damage)) = turn.attacked {
+                battle_map.hit(enemy, damage);
+            }
+            battle_map.round = turn.round;
+            battle_map.log.turns.push(turn.clone());
+        }
+        battle_map
+    }
+
+    pub fn count(&self, faction: &str) -> u32 {
+        match self.legend.factions().iter().position(|&f| f == faction) {
+            Some(faction) => self.count_faction(faction),
+            None => 0,
+        }
+    }
+
+    fn count_faction(&self, faction: usize) -> u32 {
+        self.tiles
+            .points()
+            .filter(|&point| self.faction(point) == Some(faction))
+            .count() as u32
+    }
+
+    pub fn factions_left(&self) -> Vec<&str> {
+        let mut left: Vec<usize> = self
+            .tiles
+            .points()
+            .filter_map(|point| self.faction(point))
+            .collect();
+        left.sort_unstable();
+        left.dedup();
+        let factions = self.legend.factions();
+        left.into_iter().map(|i| factions[i]).collect()
+    }
+
+    fn calculate_score(&self, rounds: u32) -> u32 {
+        let total_hit_points: u32 = self
+            .tiles
+            .values()
+            .map(|tile| match *tile {
+                Tile::Unit { hp, .. } => hp,
+                _ => 0,
+            })
+            .sum();
+        rounds * total_hit_points
+    }
+
+    pub fn battle(&mut self) -> u32 {
+        self.battle_watching(None).unwrap()
+    }
+
+    pub fn battle_without_losses(&mut self, faction: &str) -> Option<u32> {
+        // Gives up as soon as a unit of the faction dies, part way through that round
+        let faction = self.legend.factions().iter().position(|&f| f == faction);
+        self.battle_watching(faction)
+    }
+
+    fn battle_watching(&mut self, watched: Option<usize>) -> Option<u32> {
+        let mut rounds = 0;
+        loop {
+            if self.is_finished() == true {
+                // Simulation is over. Calculate final score.
+                return Some(self.calculate_score(rounds));
+            }
+
+            match self.play_turns(watched) {
+                RoundEnd::Complete => rounds += 1,
+                RoundEnd::NoEnemies => (),
+                RoundEnd::Lost => return None,
+            }
+        }
+    }
+}
+
+impl Simulation for BattleMap {
+    fn tick(&mut self) {
+        self.play_round();
+    }
+
+    fn is_finished(&self) -> bool {
+        // Quicker than listing the factions left
+        let mut factions = self.tiles.points().filter_map(|point| self.faction(point));
+        let first = factions.next();
+        factions.all(|f| Some(f) == first)
+    }
+}
+
+impl fmt::Display for BattleMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_string(false))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_legend() {
+        let legend = Legend::from_string(
+            "
+G Goblin hp=200 attack=3
+A Archer hp=100 attack=5 range=3
+",
+        );
+        assert_eq!(
+            legend.unit_types()[1],
+            UnitType {
+                symbol: 'A',
+                faction: "Archer".to_string(),
+                hit_points: 100,
+                attack: 5,
+                range: 3,
+            }
+        );
+        assert_eq!(legend.unit_types()[0].range, 1);
+        assert_eq!(Legend::default().factions(), ["Goblin", "Elf"]);
+    }
+
+    #[test]
+    fn test_three_factions() {
+        // The dwarf is stronger than either of the others, which fight each other as well as it
+        let legend = Legend::from_string(
+            "
+G Goblin hp=200 attack=3
+E Elf hp=200 attack=3
+D Dwarf hp=300 attack=9",
+        );
+        let input = "
+#######
+#G...E#
+#.....#
+#...D.#
+#######";
+        let mut battle_map = BattleMap::with_legend(input, legend);
+        assert_eq!(battle_map.factions_left(), ["Goblin", "Elf", "Dwarf"]);
+        battle_map.battle();
+        assert_eq!(battle_map.factions_left(), ["Dwarf"]);
+        assert_eq!(battle_map.count("Dwarf"), 1);
+    }
+
+    #[test]
+    fn test_range() {
+        // The archer shoots from three squares away, so the goblin is hit before it gets close and
+        // only lands one blow
+        let legend = Legend::from_string(
+            "
+G Goblin hp=10 attack=3
+A Archer hp=10 attack=4 range=3",
+        );
+        let input = "
+#######
+#G...A#
+#######";
+        let mut battle_map = BattleMap::with_legend(input, legend);
+        let mut first_round = battle_map.clone();
+        first_round.play_round();
+        assert_eq!(
+            first_round.to_string(true),
+            "#######\n#.G..A#   G(6), A(10)\n#######\n"
+        );
+        let score = battle_map.battle();
+        assert_eq!(
+            battle_map.to_string(true),
+            "#######\n#....A#   A(7)\n#######\n"
+        );
+        assert_eq!(score, 3 * 7);
+    }
+
+    #[test]
+    fn test_log() {
+        // The puzzle's examples of choosing where to go, and how to get there
+        let mut battle_map = BattleMap::from_string(
+            "
+#######
+#E..G.#
+#...#.#
+#.G.#G#
+#######",
+        );
+        battle_map.play_round();
+        let turn = &battle_map.log().turns()[0];
+        let targets = [
+            Point { x: 3, y: 1 },
+            Point { x: 2, y: 2 },
+            Point { x: 1, y: 3 },
+        ];
+        assert_eq!(turn.targets, targets);
+        assert_eq!(turn.moved_to, Some(Point { x: 2, y: 1 }));
+
+        let mut battle_map = BattleMap::from_string(
+            "
+#######
+#.E...#
+#.....#
+#...G.#
+#######",
+        );
+        battle_map.play_round();
+        let turn = &battle_map.log().turns()[0];
+        assert_eq!(turn.steps, [Point { x: 3, y: 1 }, Point { x: 2, y: 2 }]);
+        assert_eq!(
+            turn.to_string(),
+            "1 E 2,1 targets=4,2;3,3 steps=3,1;2,2 path=3,1;4,1;4,2 move=3,1 hit=-"
+        );
+    }
+
+    #[test]
+    fn test_log_paths() {
+        // The route a unit weighed survives being written out and read back in
+        let mut battle_map = BattleMap::from_string(
+            "
+#########
+#G..#...#
+#.#.#.#.#
+#.#...#E#
+#########",
+        );
+        battle_map.play_round();
+        let turn = &battle_map.log().turns()[0];
+        let path: Vec<Point> = "2,1 3,1 3,2 3,3 4,3 5,3 5,2 5,1 6,1 7,1 7,2"
+            .split(' ')
+            .map(Point::from_string)
+            .collect();
+        assert_eq!(turn.path, path);
+
+        let log = CombatLog::from_string(&battle_map.log().to_string());
+        assert_eq!(log.turns()[0].path, path);
+        assert_eq!(&log, battle_map.log());
+    }
+
+    #[test]
+    fn test_replay() {
+        let start = BattleMap::from_string(
+            "
+#######
+#.G...#
+#...EG#
+#.#.#G#
+#..G#E#
+#.....#
+#######",
+        );
+        let mut battle_map = start.clone();
+        battle_map.battle();
+
+        // Written out and read back in, the log rebuilds the board after any round
+        let text = battle_map.log().to_string();
+        let log = CombatLog::from_string(&text);
+        assert_eq!(&log, battle_map.log());
+        assert!(text.starts_with("1 G 2,1 targets=4,1;3,2 steps=3,1 path=3,1;4,1 move=3,1 hit=-\n"));
+        assert_eq!(log.round(47).count(), 5);
+        assert_eq!(log.round(48).count(), 0); // The first unit to move found no enemies
+
+        let mut played = start.clone();
+        for round in 1..=47 {
+            played.play_round();
+            let replayed = start.replay(&log, round);
+            assert_eq!(replayed.to_string(true), played.to_string(true));
+            assert_eq!(replayed.log().turns().len(), played.log().turns().len());
+        }
+        let replayed = start.replay(&log, 100);
+        assert_eq!(replayed.to_string(true), battle_map.to_string(true));
+    }
+}
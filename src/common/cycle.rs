@@ -0,0 +1,210 @@
+// Finds where a simulation starts repeating so it can skip ahead. A state may repeat exactly, or
+// repeat the same pattern moved along, e.g. plants that drift one pot right every generation.
+
+pub trait Simulation {
+    // Moves on one generation
+    fn tick(&mut self);
+
+    // For simulations that come to an end, such as a battle
+    fn is_finished(&self) -> bool {
+        false
+    }
+}
+
+pub trait Cyclic: Simulation + Clone {
+    type Key: Eq;
+
+    // States with the same key must carry on the same way, apart from the translation
+    fn key(&self) -> Self::Key;
+
+    // How far this state is translated. Leave these alone for states that don't move.
+    fn offset(&self) -> i64 {
+        0
+    }
+
+    fn translate(&mut self, _by: i64) {}
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Cycle {
+    pub start: u64,  // First generation that repeats
+    pub period: u64, // Generations between repeats
+    pub shift: i64,  // How far the state moves each period
+}
+
+fn brent<S: Cyclic>(initial: &S, limit: u64) -> Result<(Cycle, S), S> {
+    // Brent's algorithm, so only two states are kept at a time. Gives back the state at the start
+    // of the cycle, or the state at the limit if it gave up before finding one.
+    let mut tortoise = initial.key();
+    let mut hare = initial.clone();
+    let mut hare_generation = 0;
+    let mut power = 1;
+    let mut period = 0;
+    loop {
+        if hare_generation == limit {
+            return Err(hare);
+        }
+        if power == period {
+            tortoise = hare.key();
+            power *= 2;
+            period = 0;
+        }
+        hare.tick();
+        hare_generation += 1;
+        period += 1;
+        if hare.key() == tortoise {
+            break;
+        }
+    }
+
+    // The cycle starts where two states one period apart first match
+    let mut tortoise = initial.clone();
+    let mut hare = initial.clone();
+    for _ in 0..period {
+        hare.tick();
+    }
+    let mut start = 0;
+    while hare.key() != tortoise.key() {
+        tortoise.tick();
+        hare.tick();
+        start += 1;
+    }
+
+    let cycle = Cycle {
+        start,
+        period,
+        shift: hare.offset() - tortoise.offset(),
+    };
+    Ok((cycle, tortoise))
+}
+
+pub fn find_cycle<S: Cyclic>(initial: &S, limit: u64) -> Option<Cycle> {
+    // Gives up if it would need to simulate past the limit
+    brent(initial, limit).ok().map(|(cycle, _)| cycle)
+}
+
+pub fn run_to<S: Cyclic>(state: &mut S, generations: u64) {
+    // Simulates only up to the first repeat, then jumps the rest of the way
+    match brent(state, generations) {
+        Err(end) => *state = end,
+        Ok((cycle, start)) => {
+            *state = start;
+            let repeats = (generations - cycle.start) / cycle.period;
+            let remainder = (generations - cycle.start) % cycle.period;
+            for _ in 0..remainder {
+                state.tick();
+            }
+            state.translate(cycle.shift * repeats as i64);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Counts up and wraps round, like a clock
+    #[derive(Clone)]
+    struct Counter {
+        value: u64,
+        wrap_at: u64,
+        wrap_to: u64,
+    }
+
+    impl Simulation for Counter {
+        fn tick(&mut self) {
+            self.value += 1;
+            if self.value == self.wrap_at {
+                self.value = self.wrap_to;
+            }
+        }
+    }
+
+    impl Cyclic for Counter {
+        type Key = u64;
+
+        fn key(&self) -> u64 {
+            self.value
+        }
+    }
+
+    // A fuse that burns down to its last piece, then that moves right one place a generation
+    #[derive(Clone)]
+    struct Fuse {
+        length: usize,
+        position: i64,
+    }
+
+    impl Simulation for Fuse {
+        fn tick(&mut self) {
+            if self.length > 1 {
+                self.length -= 1;
+            } else {
+                self.position += 1;
+            }
+        }
+    }
+
+    impl Cyclic for Fuse {
+        type Key = usize;
+
+        fn key(&self) -> usize {
+            self.length
+        }
+
+        fn offset(&self) -> i64 {
+            self.position
+        }
+
+        fn translate(&mut self, by: i64) {
+            self.position += by;
+        }
+    }
+
+    #[test]
+    fn test_find_cycle() {
+        let counter = Counter {
+            value: 0,
+            wrap_at: 10,
+            wrap_to: 3,
+        };
+        assert_eq!(
+            find_cycle(&counter, 100),
+            Some(Cycle {
+                start: 3,
+                period: 7,
+                shift: 0
+            })
+        );
+        assert_eq!(find_cycle(&counter, 5), None);
+
+        let mut counter = counter;
+        run_to(&mut counter, 1_000_000_000_000);
+        assert_eq!(counter.value, 3 + (1_000_000_000_000 - 3) % 7);
+    }
+
+    #[test]
+    fn test_translation() {
+        let fuse = Fuse {
+            length: 5,
+            position: 0,
+        };
+        assert_eq!(
+            find_cycle(&fuse, 100),
+            Some(Cycle {
+                start: 4,
+                period: 1,
+                shift: 1
+            })
+        );
+
+        let mut expected = fuse.clone();
+        for _ in 0..100 {
+            expected.tick();
+        }
+        let mut skipped = fuse;
+        run_to(&mut skipped, 100);
+        assert_eq!(skipped.length, 1);
+        assert_eq!(skipped.position, expected.position);
+    }
+}
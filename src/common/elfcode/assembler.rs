@@ -0,0 +1,248 @@
+use super::{ChronalComputer, Instruction, Word};
+use std::collections::HashMap;
+
+// Assembly source is one instruction per line, with some extras on top of the raw puzzle format:
+//   ; comment          Everything after a semicolon is ignored
+//   #ip 5              Binds the IP, must come before any instructions
+//   loop: addi B 1 B   Labels name the line they're on, or the next instruction if alone
+//   seti loop-1 0 ip   Values may be labels with an optional offset. Jumping through the IP
+//                      needs the line before the target, as the IP is incremented afterwards.
+//   mulr A ip C        Registers may be named A-Z, ip (when bound) or rN, as well as by number
+//   07  addr B A A     An optional leading line number is checked, so disassembly reads back in
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AssemblyError {
+    // Each holds the source line number (from 1) of the error
+    Syntax(usize),
+    Directive(usize),
+    UnknownInstruction(usize),
+    UnknownRegister(usize),
+    UnknownLabel(usize),
+    DuplicateLabel(usize),
+    LineNumber(usize),
+    Value(usize),
+}
+
+struct SourceLine<'a> {
+    line: usize,
+    name: &'a str,
+    operands: [&'a str; 3],
+}
+
+fn is_label(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() == true || c == '_' => {
+            chars.all(|c| c.is_ascii_alphanumeric() == true || c == '_')
+        }
+        _ => false,
+    }
+}
+
+fn parse_register(token: &str, ip_reg: Option<usize>, line: usize) -> Result<usize, AssemblyError> {
+    let bytes = token.as_bytes();
+    let reg = if token == "ip" {
+        ip_reg
+    } else if bytes.len() == 1 && bytes[0].is_ascii_uppercase() == true {
+        Some((bytes[0] - b'A') as usize)
+    } else if let Some(number) = token.strip_prefix('r') {
+        number.parse().ok()
+    } else {
+        token.parse().ok()
+    };
+    reg.ok_or(AssemblyError::UnknownRegister(line))
+}
+
+fn parse_value<W: Word>(
+    token: &str,
+    labels: &HashMap<&str, usize>,
+    line: usize,
+) -> Result<W, AssemblyError> {
+    if token.as_bytes()[0].is_ascii_digit() == true {
+        return token.parse().map_err(|_| AssemblyError::Value(line));
+    }
+
+    // A label, optionally followed by +N or -N
+    let (label, offset) = match token.find(['+', '-']) {
+        Some(i) => (&token[..i], &token[i..]),
+        None => (token, ""),
+    };
+    let target = *labels.get(label).ok_or(AssemblyError::UnknownLabel(line))?;
+    let value = match (offset.chars().next(), offset.get(1..)) {
+        (None, _) => Some(target),
+        (Some(sign), Some(amount)) => {
+            let amount: usize = amount.parse().map_err(|_| AssemblyError::Syntax(line))?;
+            if sign == '+' {
+                target.checked_add(amount)
+            } else {
+                target.checked_sub(amount)
+            }
+        }
+        _ => None,
+    };
+    value.and_then(W::from).ok_or(AssemblyError::Value(line))
+}
+
+pub fn assemble<W: Word>(
+    source: &str,
+) -> Result<(Option<usize>, Vec<Instruction<W>>), AssemblyError> {
+    // First pass finds the IP binding, labels, and the text of each instruction
+    let mut ip_reg = None;
+    let mut labels: HashMap<&str, usize> = HashMap::new();
+    let mut lines: Vec<SourceLine> = Vec::new();
+    for (index, text) in source.lines().enumerate() {
+        let line = index + 1;
+        let text = text.split(';').next().unwrap().trim();
+        let mut tokens: Vec<&str> = text.split_whitespace().collect();
+        if tokens.is_empty() == true {
+            continue;
+        }
+
+        if tokens[0].starts_with('#') {
+            if tokens[0] != "#ip"
+                || tokens.len() != 2
+                || ip_reg.is_some()
+                || lines.is_empty() == false
+            {
+                return Err(AssemblyError::Directive(line));
+            }
+            ip_reg = Some(parse_register(tokens[1], None, line)?);
+            continue;
+        }
+
+        while let Some(label) = tokens.first().and_then(|t| t.strip_suffix(':')) {
+            if is_label(label) == false {
+                return Err(AssemblyError::Syntax(line));
+            }
+            if labels.insert(label, lines.len()).is_some() {
+                return Err(AssemblyError::DuplicateLabel(line));
+            }
+            tokens.remove(0);
+        }
+
+        if let Some(number) = tokens.first().filter(|t| t.as_bytes()[0].is_ascii_digit()) {
+            if number.parse::<usize>() != Ok(lines.len()) {
+                return Err(AssemblyError::LineNumber(line));
+            }
+            tokens.remove(0);
+        }
+
+        match tokens[..] {
+            [] => (),
+            [name, a, b, c] => lines.push(SourceLine {
+                line,
+                name,
+                operands: [a, b, c],
+            }),
+            _ => return Err(AssemblyError::Syntax(line)),
+        }
+    }
+
+    // Second pass resolves the operands, now that every label is known
+    let mut program = Vec::new();
+    for SourceLine {
+        line,
+        name,
+        operands,
+    } in lines
+    {
+        let opcode =
+            Instruction::<W>::function_code(name).ok_or(AssemblyError::UnknownInstruction(line))?;
+        let mut instr = Instruction {
+            opcode,
+            input_a: W::zero(),
+            input_b: W::zero(),
+            output_c: W::zero(),
+        };
+
+        let (reads_a, reads_b) = instr.register_inputs();
+        let operand = |token: &str, is_register: bool| {
+            if is_register == true {
+                parse_register(token, ip_reg, line)
+                    .and_then(|reg| W::from(reg).ok_or(AssemblyError::Value(line)))
+            } else {
+                parse_value(token, &labels, line)
+            }
+        };
+        instr.input_a = operand(operands[0], reads_a)?;
+        instr.input_b = operand(operands[1], reads_b)?;
+        instr.output_c = operand(operands[2], true)?;
+        program.push(instr);
+    }
+
+    Ok((ip_reg, program))
+}
+
+impl<W: Word, const N: usize> ChronalComputer<W, N> {
+    pub fn from_assembly(source: &str) -> Result<Self, AssemblyError> {
+        let (ip_reg, program) = assemble(source)?;
+        if ip_reg.is_some_and(|reg| reg >= N) {
+            let line = source
+                .lines()
+                .position(|l| l.trim_start().starts_with("#ip"));
+            return Err(AssemblyError::Directive(line.unwrap() + 1));
+        }
+        Ok(Self::new(program, ip_reg))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_assemble() {
+        let source = "\
+; Count A up to 3
+#ip 1
+        seti 0 0 A
+loop:   addi A 1 A      ; A++
+        eqri A 3 C
+        addr C ip ip    ; Skip the next line when done
+        seti loop-1 0 ip
+done:";
+        let expected = "\
+#ip 1
+seti 0 0 0
+addi 0 1 0
+eqri 0 3 2
+addr 2 1 1
+seti 0 0 1";
+        let chronal = ChronalComputer::<u32, 4>::from_assembly(source).unwrap();
+        let plain = ChronalComputer::<u32, 4>::from_string(expected);
+        assert_eq!(chronal.ip_reg, Some(1));
+        assert_eq!(chronal.program, plain.program);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let input = include_str!("../../../input/2018/day21.txt");
+        let chronal = ChronalComputer::<u64, 6>::from_string(input);
+        let listing = chronal.disassemble();
+        let (ip_reg, program) = assemble::<u64>(&listing).unwrap();
+        assert_eq!(ip_reg, chronal.ip_reg);
+        assert_eq!(program, chronal.program);
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = |source: &str| assemble::<u32>(source).unwrap_err();
+        assert_eq!(error("seti 0 0"), AssemblyError::Syntax(1));
+        assert_eq!(error("seti 0 0 0\n#ip 1"), AssemblyError::Directive(2));
+        assert_eq!(error("jump 0 0 0"), AssemblyError::UnknownInstruction(1));
+        assert_eq!(error("addr A ip A"), AssemblyError::UnknownRegister(1));
+        assert_eq!(error("seti nowhere 0 A"), AssemblyError::UnknownLabel(1));
+        assert_eq!(
+            error("x: seti 0 0 A\nx: seti 0 0 A"),
+            AssemblyError::DuplicateLabel(2)
+        );
+        assert_eq!(
+            error("00  seti 0 0 A\n02  seti 0 0 A"),
+            AssemblyError::LineNumber(2)
+        );
+        assert_eq!(error("start: seti start-1 0 A"), AssemblyError::Value(1));
+
+        let result = ChronalComputer::<u32, 4>::from_assembly("; Too few registers\n#ip 5");
+        assert_eq!(result.err(), Some(AssemblyError::Directive(2)));
+    }
+}
@@ -0,0 +1,442 @@
+use super::{ChronalComputer, Word};
+use std::collections::BTreeSet;
+use std::fmt;
+
+pub struct BasicBlock {
+    pub start: usize, // First line
+    pub end: usize,   // One past the last line
+    pub successors: Vec<usize>,
+    pub exits: bool,   // Control can leave the program from this block
+    pub dynamic: bool, // The final jump depends on runtime values, so the successors are a guess
+}
+
+pub struct Loop {
+    pub header: usize,
+    pub latches: Vec<usize>, // Blocks that jump back to the header
+    pub body: BTreeSet<usize>,
+}
+
+pub struct ControlFlowGraph {
+    pub blocks: Vec<BasicBlock>,
+    pub dominators: Vec<BTreeSet<usize>>, // Empty for blocks that can't be reached
+    pub loops: Vec<Loop>,
+    listing: Vec<String>,
+}
+
+impl ControlFlowGraph {
+    pub fn block_of(&self, line: usize) -> usize {
+        self.blocks
+            .iter()
+            .position(|block| block.start <= line && line < block.end)
+            .expect("Line is outside the program")
+    }
+
+    pub fn predecessors(&self, block: usize) -> Vec<usize> {
+        (0..self.blocks.len())
+            .filter(|&b| self.blocks[b].successors.contains(&block))
+            .collect()
+    }
+
+    pub fn immediate_dominator(&self, block: usize) -> Option<usize> {
+        // The strict dominator that is dominated by all the others, i.e. the one with the most dominators itself
+        self.dominators[block]
+            .iter()
+            .filter(|&&d| d != block)
+            .max_by_key(|&&d| self.dominators[d].len())
+            .copied()
+    }
+
+    fn is_back_edge(&self, from: usize, to: usize) -> bool {
+        self.dominators[from].contains(&to)
+    }
+
+    fn find_dominators(blocks: &[BasicBlock]) -> Vec<BTreeSet<usize>> {
+        // Find the blocks reachable from the entry, only those take part
+        let mut reachable: BTreeSet<usize> = BTreeSet::new();
+        let mut frontier = vec![0];
+        while let Some(b) = frontier.pop() {
+            if reachable.insert(b) == true {
+                frontier.extend(blocks[b].successors.iter());
+            }
+        }
+
+        // Iterate until stable: each block is dominated by itself plus whatever dominates all its predecessors
+        let mut dominators: Vec<BTreeSet<usize>> = (0..blocks.len())
+            .map(|b| {
+                if b == 0 {
+                    [0].iter().copied().collect()
+                } else if reachable.contains(&b) == true {
+                    reachable.clone()
+                } else {
+                    BTreeSet::new()
+                }
+            })
+            .collect();
+
+        let mut changed = true;
+        while changed == true {
+            changed = false;
+            for &b in reachable.iter().skip(1) {
+                let mut dom: Option<BTreeSet<usize>> = None;
+                for p in (0..blocks.len()).filter(|&p| blocks[p].successors.contains(&b)) {
+                    if reachable.contains(&p) == false {
+                        continue;
+                    }
+                    dom = Some(match dom {
+                        Some(d) => d.intersection(&dominators[p]).copied().collect(),
+                        None => dominators[p].clone(),
+                    });
+                }
+
+                let mut dom = dom.unwrap_or_default();
+                dom.insert(b);
+                if dom != dominators[b] {
+                    dominators[b] = dom;
+                    changed = true;
+                }
+            }
+        }
+
+        dominators
+    }
+
+    fn find_loops(&self) -> Vec<Loop> {
+        // Every back edge makes a natural loop: the header plus everything that reaches the latch without
+        // passing through the header. Loops sharing a header are merged.
+        let mut loops: Vec<Loop> = Vec::new();
+        for latch in 0..self.blocks.len() {
+            for &header in &self.blocks[latch].successors {
+                if self.is_back_edge(latch, header) == false {
+                    continue;
+                }
+
+                let mut body: BTreeSet<usize> = [header].iter().copied().collect();
+                let mut frontier = vec![latch];
+                while let Some(b) = frontier.pop() {
+                    if body.insert(b) == true {
+                        frontier.extend(self.predecessors(b));
+                    }
+                }
+
+                if let Some(existing) = loops.iter_mut().find(|l| l.header == header) {
+                    existing.latches.push(latch);
+                    existing.body.extend(body);
+                } else {
+                    loops.push(Loop {
+                        header,
+                        latches: vec![latch],
+                        body,
+                    });
+                }
+            }
+        }
+
+        // Innermost loops first
+        loops.sort_by_key(|l| (l.body.len(), l.header));
+        loops
+    }
+
+    pub fn to_dot(&self) -> String {
+        // Back edges are drawn red and guessed (dynamic) edges dashed
+        let mut output = String::new();
+        output += "digraph elfcode {\n";
+        output += "    node [shape=box, fontname=\"monospace\"];\n";
+        output += "    exit [shape=doublecircle];\n";
+
+        for (b, block) in self.blocks.iter().enumerate() {
+            let mut label = format!("block {}", b);
+            if let Some(idom) = self.immediate_dominator(b) {
+                label += &format!(" (idom {})", idom);
+            }
+            label += "\\l";
+            for line in block.start..block.end {
+                label += &self.listing[line].replace('"', "\\\"");
+                label += "\\l";
+            }
+            output += &format!("    b{} [label=\"{}\"];\n", b, label);
+        }
+
+        for (b, block) in self.blocks.iter().enumerate() {
+            let style = if block.dynamic == true {
+                " [style=dashed]"
+            } else {
+                ""
+            };
+            for &s in &block.successors {
+                if self.is_back_edge(b, s) == true {
+                    output += &format!("    b{} -> b{} [color=red];\n", b, s);
+                } else {
+                    output += &format!("    b{} -> b{}{};\n", b, s, style);
+                }
+            }
+            if block.exits == true {
+                output += &format!("    b{} -> exit{};\n", b, style);
+            }
+        }
+
+        output += "}\n";
+        output
+    }
+}
+
+impl fmt::Display for ControlFlowGraph {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (b, block) in self.blocks.iter().enumerate() {
+            let mut successors: Vec<String> =
+                block.successors.iter().map(|s| s.to_string()).collect();
+            if block.exits == true {
+                successors.push("exit".to_string());
+            }
+            write!(
+                f,
+                "block {}: lines {}..{} -> {}",
+                b,
+                block.start,
+                block.end,
+                successors.join(", ")
+            )?;
+            if block.dynamic == true {
+                write!(f, " (dynamic)")?;
+            }
+            if let Some(idom) = self.immediate_dominator(b) {
+                write!(f, ", idom {}", idom)?;
+            }
+            writeln!(f)?;
+        }
+
+        for l in &self.loops {
+            let body: Vec<String> = l.body.iter().map(|b| b.to_string()).collect();
+            writeln!(f, "loop at block {}: {}", l.header, body.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Word, const N: usize> ChronalComputer<W, N> {
+    pub fn control_flow_graph(&self) -> ControlFlowGraph {
+        let len = self.program.len();
+        let targets: Vec<Option<Vec<usize>>> = (0..len).map(|l| self.jump_targets(l)).collect();
+
+        // A block starts at the beginning, at every jump target and after every jump
+        let mut leaders: BTreeSet<usize> = BTreeSet::new();
+        leaders.insert(0);
+        for line in (0..len).filter(|&l| self.writes_ip(l) == true) {
+            leaders.insert(line + 1);
+            if let Some(t) = &targets[line] {
+                leaders.extend(t.iter());
+            }
+        }
+        let leaders: Vec<usize> = leaders.into_iter().filter(|&l| l < len).collect();
+
+        let mut blocks: Vec<BasicBlock> = Vec::new();
+        for (i, &start) in leaders.iter().enumerate() {
+            let end = leaders.get(i + 1).copied().unwrap_or(len);
+            let last = end - 1;
+
+            let (lines, dynamic) = match &targets[last] {
+                Some(t) => (t.clone(), false),
+                None => {
+                    // Unknown target. Adding to the IP can only go forwards, otherwise assume anywhere.
+                    let instr = &self.program[last];
+                    let (reads_a, reads_b) = instr.register_inputs();
+                    let reads_ip = |reads: bool, x: W| reads == true && x.to_usize() == self.ip_reg;
+                    let forwards = (instr.opcode == 0 || instr.opcode == 1)
+                        && (reads_ip(reads_a, instr.input_a) || reads_ip(reads_b, instr.input_b));
+                    let mut lines: Vec<usize> = leaders
+                        .iter()
+                        .copied()
+                        .filter(|&l| forwards == false || l > last)
+                        .collect();
+                    lines.push(len);
+                    (lines, true)
+                }
+            };
+
+            blocks.push(BasicBlock {
+                start,
+                end,
+                successors: lines
+                    .iter()
+                    .filter(|&&l| l < len)
+                    .map(|l| leaders.binary_search(l).unwrap())
+                    .collect(),
+                exits: lines.iter().any(|&l| l >= len),
+                dynamic,
+            });
+        }
+
+        let dominators = ControlFlowGraph::find_dominators(&blocks);
+        let mut cfg = ControlFlowGraph {
+            blocks,
+            dominators,
+            loops: Vec::new(),
+            listing: (0..len).map(|l| self.disassemble_line(l)).collect(),
+        };
+        cfg.loops = cfg.find_loops();
+        cfg
+    }
+
+    pub fn exit_conditions(&self) -> Vec<usize> {
+        // Lines with a comparison that decides whether the following jump leaves the program
+        (1..self.program.len())
+            .filter(|&line| {
+                self.program[line - 1].is_comparison() == true
+                    && match self.jump_targets(line) {
+                        Some(targets) => {
+                            targets.iter().any(|&t| t >= self.program.len())
+                                && targets.iter().any(|&t| t < self.program.len())
+                        }
+                        None => false,
+                    }
+            })
+            .map(|line| line - 1)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const PROGRAM: &str = "\
+#ip 2
+seti 123 0 3
+bani 3 456 3
+eqri 3 72 3
+addr 3 2 2
+seti 0 0 2
+seti 0 6 3
+bori 3 65536 4
+seti 7041048 8 3
+bani 4 255 5
+addr 3 5 3
+bani 3 16777215 3
+muli 3 65899 3
+bani 3 16777215 3
+gtir 256 4 5
+addr 5 2 2
+addi 2 1 2
+seti 27 6 2
+seti 0 1 5
+addi 5 1 1
+muli 1 256 1
+gtrr 1 4 1
+addr 1 2 2
+addi 2 1 2
+seti 25 1 2
+addi 5 1 5
+seti 17 8 2
+setr 5 2 4
+seti 7 9 2
+eqrr 3 0 5
+addr 5 2 2
+seti 5 3 2";
+
+    #[test]
+    fn test_blocks() {
+        let chronal = ChronalComputer::<u64, 6>::from_string(PROGRAM);
+        let cfg = chronal.control_flow_graph();
+        let ranges: Vec<(usize, usize)> = cfg.blocks.iter().map(|b| (b.start, b.end)).collect();
+        assert_eq!(
+            ranges,
+            vec![
+                (0, 1),
+                (1, 4),
+                (4, 5),
+                (5, 6),
+                (6, 8),
+                (8, 15),
+                (15, 16),
+                (16, 17),
+                (17, 18),
+                (18, 22),
+                (22, 23),
+                (23, 24),
+                (24, 26),
+                (26, 28),
+                (28, 30),
+                (30, 31),
+            ]
+        );
+        assert_eq!(cfg.blocks[1].successors, vec![2, 3]);
+        assert_eq!(cfg.blocks[14].successors, vec![15]);
+        assert!(cfg.blocks[14].exits);
+        assert_eq!(cfg.block_of(28), 14);
+        assert_eq!(cfg.predecessors(4), vec![3, 15]);
+    }
+
+    #[test]
+    fn test_dominators() {
+        let chronal = ChronalComputer::<u64, 6>::from_string(PROGRAM);
+        let cfg = chronal.control_flow_graph();
+        assert_eq!(cfg.immediate_dominator(0), None);
+        assert_eq!(cfg.immediate_dominator(1), Some(0));
+        assert_eq!(cfg.immediate_dominator(3), Some(1));
+        assert_eq!(cfg.immediate_dominator(5), Some(4));
+        assert_eq!(cfg.immediate_dominator(14), Some(7));
+        assert_eq!(
+            cfg.dominators[14],
+            [0, 1, 3, 4, 5, 7, 14].iter().copied().collect()
+        );
+    }
+
+    #[test]
+    fn test_loops() {
+        let chronal = ChronalComputer::<u64, 6>::from_string(PROGRAM);
+        let cfg = chronal.control_flow_graph();
+        let loops: Vec<(usize, Vec<usize>)> = cfg
+            .loops
+            .iter()
+            .map(|l| (l.header, l.body.iter().copied().collect()))
+            .collect();
+        assert_eq!(
+            loops,
+            vec![
+                (1, vec![1, 2]),
+                (9, vec![9, 10, 12]),
+                (5, vec![5, 6, 8, 9, 10, 11, 12, 13]),
+                (4, vec![4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_exit_conditions() {
+        let chronal = ChronalComputer::<u64, 6>::from_string(PROGRAM);
+        assert_eq!(chronal.exit_conditions(), vec![28]);
+    }
+
+    #[test]
+    fn test_dynamic() {
+        let input = "\
+#ip 1
+addr 1 0 1
+seti 5 0 2
+seti 0 0 1";
+        let chronal = ChronalComputer::<u32, 3>::from_string(input);
+        let cfg = chronal.control_flow_graph();
+        assert_eq!(cfg.blocks.len(), 2);
+        assert!(cfg.blocks[0].dynamic);
+        assert_eq!(cfg.blocks[0].successors, vec![1]);
+        assert!(cfg.blocks[0].exits);
+        assert_eq!(cfg.blocks[1].successors, vec![1]);
+        assert_eq!(cfg.loops.len(), 1);
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let input = "\
+#ip 1
+seti 5 0 2
+gtri 2 3 0
+addr 0 1 1
+seti 0 0 1";
+        let chronal = ChronalComputer::<u32, 3>::from_string(input);
+        let dot = chronal.control_flow_graph().to_dot();
+        assert!(dot.starts_with("digraph elfcode {\n"));
+        assert!(dot.contains("    b0 [label=\"block 0\\l00  seti 5 0 2"));
+        assert!(dot.contains("    b1 -> exit;\n"));
+        assert!(dot.contains("    b2 -> b1 [color=red];\n"));
+        assert!(dot.ends_with("}\n"));
+    }
+}
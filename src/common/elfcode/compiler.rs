@@ -0,0 +1,214 @@
+use super::{ChronalComputer, Error, Instruction, State, Word};
+
+// A compiled line runs its instruction (or a whole fast path) on the registers and returns the next IP
+pub(super) type CompiledLine<W, const N: usize> = Box<dyn Fn(&mut State<W, N>) -> usize>;
+
+fn flag<W: Word>(condition: bool) -> W {
+    if condition {
+        W::one()
+    } else {
+        W::zero()
+    }
+}
+
+fn next_ip<W: Word>(value: W) -> usize {
+    // Same as the interpreter, a value too big to be an index halts the program
+    value
+        .to_usize()
+        .and_then(|ip| ip.checked_add(1))
+        .unwrap_or(usize::MAX)
+}
+
+fn link<W: Word, const N: usize>(
+    op: impl Fn(&mut State<W, N>) + 'static,
+    line: usize,
+    ip_reg: Option<usize>,
+    c: usize,
+) -> CompiledLine<W, N> {
+    // Wraps the operation with the IP handling. Only a line that writes the IP register needs to
+    // read it back, everything else just moves on to the next line.
+    match ip_reg {
+        None => Box::new(move |s| {
+            op(s);
+            line + 1
+        }),
+        Some(reg) if reg == c => {
+            let ip = W::from(line).unwrap();
+            Box::new(move |s| {
+                s[reg] = ip;
+                op(s);
+                next_ip(s[reg])
+            })
+        }
+        Some(reg) => {
+            let ip = W::from(line).unwrap();
+            Box::new(move |s| {
+                s[reg] = ip;
+                op(s);
+                line + 1
+            })
+        }
+    }
+}
+
+fn compile_instruction<W: Word, const N: usize>(
+    instr: &Instruction<W>,
+    line: usize,
+    ip_reg: Option<usize>,
+) -> Result<CompiledLine<W, N>, Error> {
+    instr.validate_opcode()?;
+    let c = instr.validate_reg_c::<N>()?;
+    let (val_a, val_b) = (instr.input_a, instr.input_b);
+    let reg_a = || instr.validate_reg_a::<N>();
+    let reg_b = || instr.validate_reg_b::<N>();
+
+    let compiled = match instr.opcode {
+        0 => {
+            let (a, b) = (reg_a()?, reg_b()?);
+            link(move |s| s[c] = s[a] + s[b], line, ip_reg, c)
+        }
+        1 => {
+            let a = reg_a()?;
+            link(move |s| s[c] = s[a] + val_b, line, ip_reg, c)
+        }
+        2 => {
+            let (a, b) = (reg_a()?, reg_b()?);
+            link(move |s| s[c] = s[a] * s[b], line, ip_reg, c)
+        }
+        3 => {
+            let a = reg_a()?;
+            link(move |s| s[c] = s[a] * val_b, line, ip_reg, c)
+        }
+        4 => {
+            let (a, b) = (reg_a()?, reg_b()?);
+            link(move |s| s[c] = s[a] & s[b], line, ip_reg, c)
+        }
+        5 => {
+            let a = reg_a()?;
+            link(move |s| s[c] = s[a] & val_b, line, ip_reg, c)
+        }
+        6 => {
+            let (a, b) = (reg_a()?, reg_b()?);
+            link(move |s| s[c] = s[a] | s[b], line, ip_reg, c)
+        }
+        7 => {
+            let a = reg_a()?;
+            link(move |s| s[c] = s[a] | val_b, line, ip_reg, c)
+        }
+        8 => {
+            let a = reg_a()?;
+            link(move |s| s[c] = s[a], line, ip_reg, c)
+        }
+        9 => link(move |s| s[c] = val_a, line, ip_reg, c),
+        10 => {
+            let b = reg_b()?;
+            link(move |s| s[c] = flag(val_a > s[b]), line, ip_reg, c)
+        }
+        11 => {
+            let a = reg_a()?;
+            link(move |s| s[c] = flag(s[a] > val_b), line, ip_reg, c)
+        }
+        12 => {
+            let (a, b) = (reg_a()?, reg_b()?);
+            link(move |s| s[c] = flag(s[a] > s[b]), line, ip_reg, c)
+        }
+        13 => {
+            let b = reg_b()?;
+            link(move |s| s[c] = flag(val_a == s[b]), line, ip_reg, c)
+        }
+        14 => {
+            let a = reg_a()?;
+            link(move |s| s[c] = flag(s[a] == val_b), line, ip_reg, c)
+        }
+        15 => {
+            let (a, b) = (reg_a()?, reg_b()?);
+            link(move |s| s[c] = flag(s[a] == s[b]), line, ip_reg, c)
+        }
+        _ => unreachable!(),
+    };
+    Ok(compiled)
+}
+
+impl<W: Word, const N: usize> ChronalComputer<W, N> {
+    pub fn compile(&mut self) -> Result<(), Error> {
+        // Translates every line to a closure, so the registers are validated once here rather than
+        // on every step. Any fast paths are included, and it's redone if optimize() runs later.
+        // The program shouldn't be changed after this.
+        let compiled =
+            (0..self.program.len())
+                .map(|line| match self.fast_path_at(line) {
+                    Some(&fast_path) => Ok(Box::new(move |s: &mut State<W, N>| fast_path.apply(s))
+                        as CompiledLine<W, N>),
+                    None => compile_instruction(&self.program[line], line, self.ip_reg),
+                })
+                .collect::<Result<Vec<_>, Error>>()?;
+
+        self.compiled = compiled;
+        Ok(())
+    }
+
+    pub fn is_compiled(&self) -> bool {
+        self.compiled.is_empty() == false
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_compile() {
+        // Every function code, with the IP bound so some results feed back into it
+        let input = "\
+#ip 5
+seti 7 0 0
+setr 0 0 1
+addr 0 1 2
+addi 2 3 3
+mulr 2 3 4
+muli 4 2 4
+banr 4 3 3
+bani 4 12 2
+borr 2 1 1
+bori 1 64 1
+gtir 100 1 2
+gtri 1 70 3
+gtrr 1 0 4
+eqir 71 1 2
+eqri 1 71 3
+eqrr 2 3 4
+addr 4 5 5
+seti 99 0 0
+mulr 5 5 5";
+        let mut plain = ChronalComputer::<u32, 6>::from_string(input);
+        let mut compiled = ChronalComputer::<u32, 6>::from_string(input);
+        compiled.compile().unwrap();
+        assert!(compiled.is_compiled());
+
+        while plain.is_halted() == false {
+            plain.step().unwrap();
+            compiled.step().unwrap();
+            assert_eq!(plain.state, compiled.state);
+            assert_eq!(plain.ip, compiled.ip);
+        }
+        assert!(compiled.is_halted());
+        assert_eq!(compiled.state[0], 7);
+    }
+
+    #[test]
+    fn test_compile_errors() {
+        let mut chronal = ChronalComputer::<u32, 4>::from_string("seti 5 0 1\naddr 0 4 1");
+        assert_eq!(chronal.compile(), Err(Error::InputB));
+        assert!(chronal.is_compiled() == false);
+    }
+
+    #[test]
+    fn test_compile_fast_path() {
+        let input = include_str!("../../../input/2018/day19.txt");
+        let mut chronal = ChronalComputer::<u32, 6>::from_string(input);
+        chronal.compile().unwrap();
+        chronal.optimize();
+        chronal.run().unwrap();
+        assert_eq!(chronal.state[0], 1302);
+    }
+}
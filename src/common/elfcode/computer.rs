@@ -0,0 +1,151 @@
+use super::compiler::CompiledLine;
+use super::{Error, FastPath, Instruction, State, Word};
+use nom::{
+    bytes::complete::tag,
+    character::complete::{digit1, multispace0},
+    combinator::{map_res, opt},
+    multi::many1,
+    sequence::{pair, preceded},
+    IResult,
+};
+
+pub struct ChronalComputer<W, const N: usize> {
+    pub state: State<W, N>,
+    pub program: Vec<Instruction<W>>,
+    pub ip_reg: Option<usize>,
+    pub ip: usize,
+    pub(super) fast_paths: Vec<Option<FastPath<W>>>, // Indexed by line, empty until optimized
+    pub(super) compiled: Vec<CompiledLine<W, N>>,    // Indexed by line, empty until compiled
+}
+
+impl<W: Word, const N: usize> ChronalComputer<W, N> {
+    pub fn new(program: Vec<Instruction<W>>, ip_reg: Option<usize>) -> Self {
+        if let Some(reg) = ip_reg {
+            assert!(reg < N, "IP bound to invalid register {}", reg);
+        }
+
+        Self {
+            state: State::new(),
+            program,
+            ip_reg,
+            ip: 0,
+            fast_paths: Vec::new(),
+            compiled: Vec::new(),
+        }
+    }
+
+    pub fn from_string(input: &str) -> Self {
+        let (remaining, computer) = Self::parser(input).unwrap();
+        assert!(
+            remaining.trim().is_empty(),
+            "Invalid program at: {}",
+            remaining.trim().lines().next().unwrap()
+        );
+        computer
+    }
+
+    pub fn parser(input: &str) -> IResult<&str, Self> {
+        // The #ip directive is optional, without it the IP is not visible to the program
+        let (input, (ip_reg, program)) = pair(
+            opt(preceded(
+                pair(multispace0, tag("#ip ")),
+                map_res(digit1, |x: &str| x.parse::<usize>()),
+            )),
+            many1(Instruction::parser),
+        )(input)?;
+
+        Ok((input, Self::new(program, ip_reg)))
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.ip >= self.program.len()
+    }
+
+    pub fn step(&mut self) -> Result<(), Error> {
+        if self.is_halted() == true {
+            return Err(Error::Halted);
+        }
+
+        // Compiled lines handle the IP themselves, and include any fast paths
+        if let Some(line) = self.compiled.get(self.ip) {
+            self.ip = line(&mut self.state);
+            return Ok(());
+        }
+
+        // Run a whole idiom at once if one starts here
+        if let Some(Some(fast_path)) = self.fast_paths.get(self.ip) {
+            self.ip = fast_path.apply(&mut self.state);
+            return Ok(());
+        }
+
+        // Write the IP to its bound register
+        if let Some(reg) = self.ip_reg {
+            self.state[reg] = W::from(self.ip).unwrap();
+        }
+
+        // Execute the instruction
+        self.state = self.program[self.ip].dispatch(self.state)?;
+
+        // Write the register value back to the IP (plus an increment). A value too big to be an
+        // index is out of the program anyway, so it halts.
+        self.ip = match self.ip_reg {
+            Some(reg) => self.state[reg]
+                .to_usize()
+                .and_then(|ip| ip.checked_add(1))
+                .unwrap_or(usize::MAX),
+            None => self.ip + 1,
+        };
+
+        Ok(())
+    }
+
+    pub fn run(&mut self) -> Result<(), Error> {
+        while self.is_halted() == false {
+            self.step()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_run() {
+        let input = "\
+#ip 0
+seti 5 0 1
+seti 6 0 2
+addi 0 1 0
+addr 1 2 3
+setr 1 0 0
+seti 8 0 4
+seti 9 0 5";
+        let mut chronal = ChronalComputer::<u32, 6>::from_string(input);
+        assert_eq!(chronal.ip_reg, Some(0));
+        chronal.run().unwrap();
+        assert_eq!(chronal.state, State([6, 5, 6, 0, 0, 9]));
+        assert_eq!(chronal.step(), Err(Error::Halted));
+    }
+
+    #[test]
+    fn test_step() {
+        let input = "\
+seti 5 0 1
+addi 1 2 0";
+        let mut chronal = ChronalComputer::<u64, 4>::from_string(input);
+        assert_eq!(chronal.ip_reg, None);
+        chronal.step().unwrap();
+        assert_eq!((chronal.ip, chronal.state), (1, State([0, 5, 0, 0])));
+        chronal.step().unwrap();
+        assert_eq!((chronal.ip, chronal.state), (2, State([7, 5, 0, 0])));
+        assert!(chronal.is_halted());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_invalid_ip_reg() {
+        ChronalComputer::<u32, 4>::from_string("#ip 4\nseti 0 0 0");
+    }
+}
@@ -0,0 +1,291 @@
+use super::{ChronalComputer, Error, State, Word};
+use std::collections::BTreeMap;
+use std::io;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Action {
+    Continue,
+    Break,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum StopReason {
+    Halted,
+    Breakpoint { ip: usize },
+    Watchpoint { reg: usize, ip: usize },
+}
+
+type BreakpointCallback<'a, W, const N: usize> = Box<dyn FnMut(&State<W, N>) -> Action + 'a>;
+type WatchpointCallback<'a, W> = Box<dyn FnMut(usize, W, W) -> Action + 'a>;
+
+pub struct Debugger<'a, W, const N: usize> {
+    computer: &'a mut ChronalComputer<W, N>,
+    breakpoints: BTreeMap<usize, Option<BreakpointCallback<'a, W, N>>>, // Keyed by IP
+    watchpoints: BTreeMap<usize, Option<WatchpointCallback<'a, W>>>,    // Keyed by register
+    trace: Option<Box<dyn io::Write + 'a>>,
+    resume_ip: Option<usize>, // The breakpoint just stopped at, so that running again gets past it
+}
+
+impl<'a, W: Word, const N: usize> Debugger<'a, W, N> {
+    pub fn new(computer: &'a mut ChronalComputer<W, N>) -> Self {
+        Self {
+            computer,
+            breakpoints: BTreeMap::new(),
+            watchpoints: BTreeMap::new(),
+            trace: None,
+            resume_ip: None,
+        }
+    }
+
+    pub fn computer(&self) -> &ChronalComputer<W, N> {
+        self.computer
+    }
+
+    pub fn trace_to(&mut self, output: impl io::Write + 'a) {
+        self.trace = Some(Box::new(output));
+    }
+
+    pub fn add_breakpoint(&mut self, ip: usize) {
+        // Only the first line of a fast path is ever reached. A breakpoint on any other line it
+        // covers won't be hit, so run the program without optimizing to stop inside one.
+        self.breakpoints.insert(ip, None);
+    }
+
+    pub fn on_breakpoint(&mut self, ip: usize, callback: impl FnMut(&State<W, N>) -> Action + 'a) {
+        // The callback sees the registers before the instruction at this IP runs. As with
+        // add_breakpoint, it's never called for a line inside a fast path.
+        self.breakpoints.insert(ip, Some(Box::new(callback)));
+    }
+
+    pub fn remove_breakpoint(&mut self, ip: usize) {
+        self.breakpoints.remove(&ip);
+    }
+
+    pub fn add_watchpoint(&mut self, reg: usize) {
+        self.watchpoints.insert(reg, None);
+    }
+
+    pub fn on_watchpoint(&mut self, reg: usize, callback: impl FnMut(usize, W, W) -> Action + 'a) {
+        // The callback gets the IP of the writing instruction, then the old and new values
+        self.watchpoints.insert(reg, Some(Box::new(callback)));
+    }
+
+    pub fn remove_watchpoint(&mut self, reg: usize) {
+        self.watchpoints.remove(&reg);
+    }
+
+    fn write_trace(&mut self, line: &str) -> Result<(), Error> {
+        if let Some(output) = self.trace.as_mut() {
+            writeln!(output, "{}", line).map_err(|_| Error::Trace)?;
+        }
+        Ok(())
+    }
+
+    pub fn step(&mut self) -> Result<Option<StopReason>, Error> {
+        // Runs a single instruction (or a whole fast path). Returns why the program stopped, if it did.
+        let ip = self.computer.ip;
+        let mut before = self.computer.state;
+        if let Some(reg) = self.computer.ip_reg {
+            before[reg] = W::from(ip).unwrap();
+        }
+
+        // A fast path may write to any register, otherwise it's just the instruction's output
+        let fast_path = self.computer.fast_path_at(ip).copied();
+        let written = match fast_path {
+            Some(_) => None,
+            None if self.computer.is_halted() == false => {
+                self.computer.program[ip].output_c.to_usize()
+            }
+            None => return Ok(Some(StopReason::Halted)),
+        };
+
+        let instr = self.computer.program[ip];
+        self.computer.step()?;
+        let after = self.computer.state;
+        if self.trace.is_some() {
+            let text = match fast_path {
+                Some(f) => f.name().to_string(),
+                None => format!(
+                    "{} {} {} {}",
+                    instr.name(),
+                    instr.input_a,
+                    instr.input_b,
+                    instr.output_c
+                ),
+            };
+            self.write_trace(&format!("ip={} {:?} {} {:?}", ip, before.0, text, after.0))?;
+        }
+
+        let mut stop = None;
+        for (&reg, callback) in self.watchpoints.iter_mut() {
+            let was_written = match written {
+                Some(w) => w == reg,
+                None => before[reg] != after[reg],
+            };
+            if was_written == false {
+                continue;
+            }
+
+            let action = match callback {
+                Some(callback) => callback(ip, before[reg], after[reg]),
+                None => Action::Break,
+            };
+            if action == Action::Break && stop.is_none() {
+                stop = Some(StopReason::Watchpoint { reg, ip });
+            }
+        }
+
+        if stop.is_none() && self.computer.is_halted() == true {
+            stop = Some(StopReason::Halted);
+        }
+        Ok(stop)
+    }
+
+    pub fn run(&mut self) -> Result<StopReason, Error> {
+        loop {
+            let ip = self.computer.ip;
+            if self.resume_ip.take() != Some(ip) {
+                let state = self.computer.state;
+                let action = match self.breakpoints.get_mut(&ip) {
+                    Some(Some(callback)) => callback(&state),
+                    Some(None) => Action::Break,
+                    None => Action::Continue,
+                };
+                if action == Action::Break {
+                    self.resume_ip = Some(ip);
+                    return Ok(StopReason::Breakpoint { ip });
+                }
+            }
+
+            // Nothing to check after each step, so run it directly
+            if self.watchpoints.is_empty() == true && self.trace.is_none() == true {
+                if self.computer.is_halted() == true {
+                    return Ok(StopReason::Halted);
+                }
+                self.computer.step()?;
+                continue;
+            }
+
+            if let Some(stop) = self.step()? {
+                return Ok(stop);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const PROGRAM: &str = "\
+#ip 0
+seti 5 0 1
+seti 6 0 2
+addi 0 1 0
+addr 1 2 3
+setr 1 0 0
+seti 8 0 4
+seti 9 0 5";
+
+    #[test]
+    fn test_trace() {
+        let mut chronal = ChronalComputer::<u32, 6>::from_string(PROGRAM);
+        let mut output: Vec<u8> = Vec::new();
+        {
+            let mut debugger = Debugger::new(&mut chronal);
+            debugger.trace_to(&mut output);
+            assert_eq!(debugger.run(), Ok(StopReason::Halted));
+        }
+
+        let expected = "\
+ip=0 [0, 0, 0, 0, 0, 0] seti 5 0 1 [0, 5, 0, 0, 0, 0]
+ip=1 [1, 5, 0, 0, 0, 0] seti 6 0 2 [1, 5, 6, 0, 0, 0]
+ip=2 [2, 5, 6, 0, 0, 0] addi 0 1 0 [3, 5, 6, 0, 0, 0]
+ip=4 [4, 5, 6, 0, 0, 0] setr 1 0 0 [5, 5, 6, 0, 0, 0]
+ip=6 [6, 5, 6, 0, 0, 0] seti 9 0 5 [6, 5, 6, 0, 0, 9]
+";
+        assert_eq!(String::from_utf8(output).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        let mut chronal = ChronalComputer::<u32, 6>::from_string(PROGRAM);
+        let mut debugger = Debugger::new(&mut chronal);
+        debugger.add_breakpoint(4);
+        debugger.add_breakpoint(3); // Never reached
+        assert_eq!(debugger.run(), Ok(StopReason::Breakpoint { ip: 4 }));
+        assert_eq!(debugger.computer().state.0, [3, 5, 6, 0, 0, 0]);
+
+        // Running again continues past the breakpoint
+        assert_eq!(debugger.step(), Ok(None));
+        assert_eq!(debugger.computer().ip, 6);
+        debugger.remove_breakpoint(4);
+        assert_eq!(debugger.run(), Ok(StopReason::Halted));
+        assert_eq!(debugger.run(), Ok(StopReason::Halted));
+        assert_eq!(debugger.step(), Ok(Some(StopReason::Halted)));
+    }
+
+    #[test]
+    fn test_breakpoint_callback() {
+        let mut chronal = ChronalComputer::<u32, 6>::from_string(PROGRAM);
+        let mut seen = Vec::new();
+        {
+            let mut debugger = Debugger::new(&mut chronal);
+            debugger.on_breakpoint(2, |state| {
+                seen.push(state[1]);
+                Action::Continue
+            });
+            assert_eq!(debugger.run(), Ok(StopReason::Halted));
+        }
+        assert_eq!(seen, vec![5]);
+    }
+
+    #[test]
+    fn test_watchpoints() {
+        let mut chronal = ChronalComputer::<u32, 6>::from_string(PROGRAM);
+        let mut writes = Vec::new();
+        {
+            let mut debugger = Debugger::new(&mut chronal);
+            debugger.on_watchpoint(0, |ip, old, new| {
+                writes.push((ip, old, new));
+                Action::Continue
+            });
+            debugger.add_watchpoint(5);
+            assert_eq!(debugger.run(), Ok(StopReason::Watchpoint { reg: 5, ip: 6 }));
+            assert_eq!(debugger.run(), Ok(StopReason::Halted));
+        }
+        assert_eq!(writes, vec![(2, 2, 3), (4, 4, 5)]);
+    }
+
+    #[test]
+    fn test_fast_path() {
+        // A fast path shows up as one step, and watchpoints see any register it changed
+        let input = "\
+#ip 2
+seti 1000 0 4
+seti 0 0 5
+addi 5 1 1
+muli 1 256 1
+gtrr 1 4 1
+addr 1 2 2
+addi 2 1 2
+seti 9 0 2
+addi 5 1 5
+seti 1 0 2
+setr 5 0 4";
+        let mut chronal = ChronalComputer::<u32, 6>::from_string(input);
+        chronal.optimize();
+        let mut output: Vec<u8> = Vec::new();
+        {
+            let mut debugger = Debugger::new(&mut chronal);
+            debugger.trace_to(&mut output);
+            debugger.add_watchpoint(1);
+            assert_eq!(debugger.run(), Ok(StopReason::Watchpoint { reg: 1, ip: 2 }));
+        }
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(
+            output.lines().last(),
+            Some("ip=2 [0, 0, 2, 0, 1000, 0] divide loop [0, 1, 9, 0, 1000, 3]")
+        );
+    }
+}
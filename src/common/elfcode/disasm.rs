@@ -0,0 +1,277 @@
+use super::{ChronalComputer, Instruction, State, Word};
+use std::collections::BTreeSet;
+
+fn register_name(reg: usize, ip_reg: Option<usize>) -> String {
+    if Some(reg) == ip_reg {
+        "ip".to_string()
+    } else if reg < 26 {
+        ((b'A' + reg as u8) as char).to_string()
+    } else {
+        format!("r{}", reg)
+    }
+}
+
+impl<W: Word> Instruction<W> {
+    pub fn pseudo_code(&self, ip_reg: Option<usize>) -> String {
+        let reg = |x: W| register_name(x.to_usize().unwrap_or(usize::MAX), ip_reg);
+        let (a, b, c) = (reg(self.input_a), reg(self.input_b), reg(self.output_c));
+        let (val_a, val_b) = (self.input_a.to_string(), self.input_b.to_string());
+
+        // Prefer the compound assignment form when the output is also one of the inputs
+        let binary = |lhs: &str, op: &str, rhs: &str, commutative: bool| {
+            if c == lhs {
+                format!("{} {}= {}", c, op, rhs)
+            } else if commutative == true && c == rhs {
+                format!("{} {}= {}", c, op, lhs)
+            } else {
+                format!("{} = {} {} {}", c, lhs, op, rhs)
+            }
+        };
+        let compare = |lhs: &str, op: &str, rhs: &str| format!("{} = ({} {} {})", c, lhs, op, rhs);
+
+        match self.opcode {
+            0 => binary(&a, "+", &b, true),
+            1 => binary(&a, "+", &val_b, false),
+            2 => binary(&a, "*", &b, true),
+            3 => binary(&a, "*", &val_b, false),
+            4 => binary(&a, "&", &b, true),
+            5 => binary(&a, "&", &val_b, false),
+            6 => binary(&a, "|", &b, true),
+            7 => binary(&a, "|", &val_b, false),
+            8 => format!("{} = {}", c, a),
+            9 => format!("{} = {}", c, val_a),
+            10 => compare(&val_a, ">", &b),
+            11 => compare(&a, ">", &val_b),
+            12 => compare(&a, ">", &b),
+            13 => compare(&val_a, "==", &b),
+            14 => compare(&a, "==", &val_b),
+            15 => compare(&a, "==", &b),
+            _ => "???".to_string(),
+        }
+    }
+}
+
+impl<W: Word, const N: usize> ChronalComputer<W, N> {
+    fn possible_values(&self, line: usize, reg: W, flags: bool) -> Option<Vec<W>> {
+        // The only register values known without running the program are the IP itself, and flags
+        // written by a comparison on the line just before. Flags are only trusted when the line
+        // can't be reached by a jump from somewhere else.
+        let reg = reg.to_usize()?;
+        if Some(reg) == self.ip_reg {
+            Some(vec![W::from(line)?])
+        } else if flags == true
+            && line > 0
+            && self.program[line - 1].is_comparison() == true
+            && self.program[line - 1].output_c.to_usize() == Some(reg)
+        {
+            Some(vec![W::zero(), W::one()])
+        } else {
+            None
+        }
+    }
+
+    pub fn writes_ip(&self, line: usize) -> bool {
+        self.ip_reg.is_some() && self.program[line].output_c.to_usize() == self.ip_reg
+    }
+
+    fn landing_lines(&self) -> BTreeSet<usize> {
+        // Lines some jump may land on, trusting every flag
+        (0..self.program.len())
+            .filter(|&line| self.writes_ip(line) == true)
+            .filter_map(|line| self.targets_from(line, true))
+            .flatten()
+            .collect()
+    }
+
+    pub fn jump_targets(&self, line: usize) -> Option<Vec<usize>> {
+        // Returns the lines that may run after this one, or None if that depends on runtime values.
+        // Any target past the end of the program means the program exits.
+        let landing = self.writes_ip(line) == true && self.landing_lines().contains(&line) == true;
+        self.targets_from(line, landing == false)
+    }
+
+    fn targets_from(&self, line: usize, flags: bool) -> Option<Vec<usize>> {
+        if self.writes_ip(line) == false {
+            return Some(vec![line + 1]);
+        }
+        let instr = &self.program[line];
+        let ip_reg = self.ip_reg.unwrap();
+
+        let (reads_a, reads_b) = instr.register_inputs();
+        let values_a = if reads_a == true {
+            self.possible_values(line, instr.input_a, flags)?
+        } else {
+            vec![W::zero()]
+        };
+        let values_b = if reads_b == true {
+            self.possible_values(line, instr.input_b, flags)?
+        } else {
+            vec![W::zero()]
+        };
+
+        // Run the instruction on every combination of input values
+        let mut targets = Vec::new();
+        for &value_a in &values_a {
+            for &value_b in &values_b {
+                let mut state: State<W, N> = State::new();
+                if reads_a == true {
+                    state[instr.input_a.to_usize()?] = value_a;
+                }
+                if reads_b == true {
+                    state[instr.input_b.to_usize()?] = value_b;
+                }
+                state[ip_reg] = W::from(line)?;
+
+                let result = instr.dispatch(state).ok()?;
+                let target = result[ip_reg]
+                    .to_usize()
+                    .and_then(|ip| ip.checked_add(1))
+                    .unwrap_or(usize::MAX);
+                targets.push(target);
+            }
+        }
+        targets.sort_unstable();
+        targets.dedup();
+        Some(targets)
+    }
+
+    fn jump_comment(&self, line: usize) -> String {
+        if self.writes_ip(line) == false {
+            return String::new();
+        }
+
+        if let Some(targets) = self.jump_targets(line) {
+            let exits = targets.iter().any(|&t| t >= self.program.len());
+            let mut names: Vec<String> = targets
+                .iter()
+                .filter(|&&t| t < self.program.len())
+                .map(|t| t.to_string())
+                .collect();
+            if exits == true {
+                names.push("exit".to_string());
+            }
+
+            if names == ["exit"] {
+                "Exit".to_string()
+            } else {
+                format!("Jump to line {}", names.join(" or "))
+            }
+        } else {
+            String::new()
+        }
+    }
+
+    pub fn disassemble_line(&self, line: usize) -> String {
+        let instr = &self.program[line];
+        let text = format!(
+            "{} {} {} {}",
+            instr.name(),
+            instr.input_a,
+            instr.input_b,
+            instr.output_c
+        );
+        // The annotations are a comment, so the listing can be read back in by the assembler
+        let annotated = format!(
+            "{:02}  {:<20}; {:<20}{}",
+            line,
+            text,
+            instr.pseudo_code(self.ip_reg),
+            self.jump_comment(line)
+        );
+        annotated.trim_end().to_string()
+    }
+
+    pub fn disassemble(&self) -> String {
+        let mut output = String::new();
+        if let Some(reg) = self.ip_reg {
+            output += &format!("#ip {}\n", reg);
+        }
+
+        for line in 0..self.program.len() {
+            output += &self.disassemble_line(line);
+            output += "\n";
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_pseudo_code() {
+        let code = |s: &str| Instruction::<u32>::from_string(s).pseudo_code(Some(2));
+        assert_eq!(code("seti 123 0 3"), "D = 123");
+        assert_eq!(code("bani 3 456 3"), "D &= 456");
+        assert_eq!(code("eqri 3 72 3"), "D = (D == 72)");
+        assert_eq!(code("addr 3 2 2"), "ip += D");
+        assert_eq!(code("bori 3 65536 4"), "E = D | 65536");
+        assert_eq!(code("gtir 256 4 5"), "F = (256 > E)");
+        assert_eq!(code("addi 5 1 1"), "B = F + 1");
+        assert_eq!(code("setr 5 2 4"), "E = F");
+        assert_eq!(code("mulr 2 2 2"), "ip *= ip");
+        assert_eq!(code("gtrr 1 4 1"), "B = (B > E)");
+    }
+
+    #[test]
+    fn test_jump_targets() {
+        let input = "\
+#ip 2
+seti 123 0 3
+bani 3 456 3
+eqri 3 72 3
+addr 3 2 2
+seti 0 0 2
+mulr 2 2 2
+addr 0 2 2";
+        let chronal = ChronalComputer::<u64, 6>::from_string(input);
+        assert_eq!(chronal.jump_targets(0), Some(vec![1]));
+        assert_eq!(chronal.jump_targets(3), Some(vec![4, 5]));
+        assert_eq!(chronal.jump_targets(4), Some(vec![1]));
+        assert_eq!(chronal.jump_targets(5), Some(vec![26]));
+        assert_eq!(chronal.jump_targets(6), None);
+
+        // Line 2 can also be reached from line 3, so D isn't known to be a flag there
+        let input = "\
+#ip 2
+seti 123 0 3
+eqri 3 72 3
+addr 3 2 2
+seti 1 0 2";
+        let chronal = ChronalComputer::<u64, 6>::from_string(input);
+        assert_eq!(chronal.jump_targets(3), Some(vec![2]));
+        assert_eq!(chronal.jump_targets(2), None);
+    }
+
+    #[test]
+    fn test_disassemble() {
+        let input = "\
+#ip 2
+seti 123 0 3
+bani 3 456 3
+eqri 3 72 3
+addr 3 2 2
+seti 0 0 2
+gtir 256 4 5
+addr 5 2 2
+addi 2 1 2
+mulr 2 2 2
+addr 0 2 2";
+        let chronal = ChronalComputer::<u64, 6>::from_string(input);
+        let expected = "\
+#ip 2
+00  seti 123 0 3        ; D = 123
+01  bani 3 456 3        ; D &= 456
+02  eqri 3 72 3         ; D = (D == 72)
+03  addr 3 2 2          ; ip += D             Jump to line 4 or 5
+04  seti 0 0 2          ; ip = 0              Jump to line 1
+05  gtir 256 4 5        ; F = (256 > E)
+06  addr 5 2 2          ; ip += F             Jump to line 7 or 8
+07  addi 2 1 2          ; ip += 1             Jump to line 9
+08  mulr 2 2 2          ; ip *= ip            Exit
+09  addr 0 2 2          ; ip += A
+";
+        assert_eq!(chronal.disassemble(), expected);
+    }
+}
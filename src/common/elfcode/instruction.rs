@@ -0,0 +1,407 @@
+use super::{State, Word};
+use nom::{
+    character::complete::{alpha1, char, digit1, multispace0, space1},
+    combinator::{map_opt, map_res},
+    sequence::{delimited, preceded, terminated, tuple},
+    IResult,
+};
+
+// Function codes are the index of each name in this table
+pub const FUNCTION_NAMES: [&str; 16] = [
+    "addr", "addi", "mulr", "muli", "banr", "bani", "borr", "bori", "setr", "seti", "gtir", "gtri",
+    "gtrr", "eqir", "eqri", "eqrr",
+];
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum Error {
+    Opcode,
+    Functioncode,
+    InputA,
+    InputB,
+    OutputC,
+    Halted,
+    Trace,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Instruction<W> {
+    pub opcode: u8,
+    pub input_a: W,
+    pub input_b: W,
+    pub output_c: W,
+}
+
+impl<W: Word> Instruction<W> {
+    pub fn from_string(input: &str) -> Self {
+        Self::parser(input).unwrap().1
+    }
+
+    pub fn function_code(name: &str) -> Option<u8> {
+        FUNCTION_NAMES
+            .iter()
+            .position(|&n| n == name)
+            .map(|fc| fc as u8)
+    }
+
+    pub fn parser(input: &str) -> IResult<&str, Self> {
+        // Named form, where the opcode is the function code: addi 5 16 5
+        let (input, (opcode, input_a, input_b, output_c)) = tuple((
+            preceded(multispace0, map_opt(alpha1, Self::function_code)),
+            preceded(space1, map_res(digit1, |a: &str| a.parse::<W>())),
+            preceded(space1, map_res(digit1, |b: &str| b.parse::<W>())),
+            preceded(space1, map_res(digit1, |c: &str| c.parse::<W>())),
+        ))(input)?;
+
+        Ok((
+            input,
+            Self {
+                opcode,
+                input_a,
+                input_b,
+                output_c,
+            },
+        ))
+    }
+
+    pub fn parser_numeric(input: &str) -> IResult<&str, Self> {
+        // Numeric form, where the opcode still has to be mapped to a function code: 9 2 1 2
+        let (input, (opcode, input_a, input_b, output_c)) = tuple((
+            delimited(
+                multispace0,
+                map_res(digit1, |x: &str| x.parse::<u8>()),
+                char(' '),
+            ),
+            terminated(map_res(digit1, |x: &str| x.parse::<W>()), char(' ')),
+            terminated(map_res(digit1, |x: &str| x.parse::<W>()), char(' ')),
+            map_res(digit1, |x: &str| x.parse::<W>()),
+        ))(input)?;
+
+        Ok((
+            input,
+            Self {
+                opcode,
+                input_a,
+                input_b,
+                output_c,
+            },
+        ))
+    }
+
+    pub fn name(&self) -> &'static str {
+        FUNCTION_NAMES.get(self.opcode as usize).unwrap_or(&"????")
+    }
+
+    pub fn register_inputs(&self) -> (bool, bool) {
+        // Whether A and B are read as registers, as opposed to immediate values or being ignored
+        match self.opcode {
+            0 | 2 | 4 | 6 | 12 | 15 => (true, true), // addr, mulr, banr, borr, gtrr, eqrr
+            1 | 3 | 5 | 7 | 8 | 11 | 14 => (true, false), // addi, muli, bani, bori, setr, gtri, eqri
+            10 | 13 => (false, true),                     // gtir, eqir
+            _ => (false, false),                          // seti
+        }
+    }
+
+    pub fn is_comparison(&self) -> bool {
+        // The output of these is always 0 or 1
+        (10..=15).contains(&self.opcode)
+    }
+
+    pub fn validate_opcode(&self) -> Result<(), Error> {
+        if self.opcode > 15 {
+            Err(Error::Opcode)
+        } else {
+            Ok(())
+        }
+    }
+
+    // The register validators return the register index so the opcodes don't need to convert it again
+    pub fn validate_reg_a<const N: usize>(&self) -> Result<usize, Error> {
+        Self::validate_reg::<N>(self.input_a).ok_or(Error::InputA)
+    }
+
+    pub fn validate_reg_b<const N: usize>(&self) -> Result<usize, Error> {
+        Self::validate_reg::<N>(self.input_b).ok_or(Error::InputB)
+    }
+
+    pub fn validate_reg_c<const N: usize>(&self) -> Result<usize, Error> {
+        Self::validate_reg::<N>(self.output_c).ok_or(Error::OutputC)
+    }
+
+    fn validate_reg<const N: usize>(value: W) -> Option<usize> {
+        value.to_usize().filter(|&reg| reg < N)
+    }
+
+    pub fn dispatch<const N: usize>(&self, input: State<W, N>) -> Result<State<W, N>, Error> {
+        self.validate_opcode()?;
+        self.dispatch_as(input, self.opcode)
+    }
+
+    pub fn dispatch_as<const N: usize>(
+        &self,
+        input: State<W, N>,
+        function_code: u8,
+    ) -> Result<State<W, N>, Error> {
+        match function_code {
+            0 => self.addr(input),
+            1 => self.addi(input),
+            2 => self.mulr(input),
+            3 => self.muli(input),
+            4 => self.banr(input),
+            5 => self.bani(input),
+            6 => self.borr(input),
+            7 => self.bori(input),
+            8 => self.setr(input),
+            9 => self.seti(input),
+            10 => self.gtir(input),
+            11 => self.gtri(input),
+            12 => self.gtrr(input),
+            13 => self.eqir(input),
+            14 => self.eqri(input),
+            15 => self.eqrr(input),
+            _ => Err(Error::Functioncode),
+        }
+    }
+
+    fn flag(condition: bool) -> W {
+        if condition {
+            W::one()
+        } else {
+            W::zero()
+        }
+    }
+
+    pub fn addr<const N: usize>(&self, input: State<W, N>) -> Result<State<W, N>, Error> {
+        let a = self.validate_reg_a::<N>()?;
+        let b = self.validate_reg_b::<N>()?;
+        let c = self.validate_reg_c::<N>()?;
+
+        let mut state = input;
+        state[c] = state[a] + state[b];
+        Ok(state)
+    }
+
+    pub fn addi<const N: usize>(&self, input: State<W, N>) -> Result<State<W, N>, Error> {
+        let a = self.validate_reg_a::<N>()?;
+        let c = self.validate_reg_c::<N>()?;
+
+        let mut state = input;
+        state[c] = state[a] + self.input_b;
+        Ok(state)
+    }
+
+    pub fn mulr<const N: usize>(&self, input: State<W, N>) -> Result<State<W, N>, Error> {
+        let a = self.validate_reg_a::<N>()?;
+        let b = self.validate_reg_b::<N>()?;
+        let c = self.validate_reg_c::<N>()?;
+
+        let mut state = input;
+        state[c] = state[a] * state[b];
+        Ok(state)
+    }
+
+    pub fn muli<const N: usize>(&self, input: State<W, N>) -> Result<State<W, N>, Error> {
+        let a = self.validate_reg_a::<N>()?;
+        let c = self.validate_reg_c::<N>()?;
+
+        let mut state = input;
+        state[c] = state[a] * self.input_b;
+        Ok(state)
+    }
+
+    pub fn banr<const N: usize>(&self, input: State<W, N>) -> Result<State<W, N>, Error> {
+        let a = self.validate_reg_a::<N>()?;
+        let b = self.validate_reg_b::<N>()?;
+        let c = self.validate_reg_c::<N>()?;
+
+        let mut state = input;
+        state[c] = state[a] & state[b];
+        Ok(state)
+    }
+
+    pub fn bani<const N: usize>(&self, input: State<W, N>) -> Result<State<W, N>, Error> {
+        let a = self.validate_reg_a::<N>()?;
+        let c = self.validate_reg_c::<N>()?;
+
+        let mut state = input;
+        state[c] = state[a] & self.input_b;
+        Ok(state)
+    }
+
+    pub fn borr<const N: usize>(&self, input: State<W, N>) -> Result<State<W, N>, Error> {
+        let a = self.validate_reg_a::<N>()?;
+        let b = self.validate_reg_b::<N>()?;
+        let c = self.validate_reg_c::<N>()?;
+
+        let mut state = input;
+        state[c] = state[a] | state[b];
+        Ok(state)
+    }
+
+    pub fn bori<const N: usize>(&self, input: State<W, N>) -> Result<State<W, N>, Error> {
+        let a = self.validate_reg_a::<N>()?;
+        let c = self.validate_reg_c::<N>()?;
+
+        let mut state = input;
+        state[c] = state[a] | self.input_b;
+        Ok(state)
+    }
+
+    pub fn setr<const N: usize>(&self, input: State<W, N>) -> Result<State<W, N>, Error> {
+        let a = self.validate_reg_a::<N>()?;
+        let c = self.validate_reg_c::<N>()?;
+
+        let mut state = input;
+        state[c] = state[a];
+        Ok(state)
+    }
+
+    pub fn seti<const N: usize>(&self, input: State<W, N>) -> Result<State<W, N>, Error> {
+        let c = self.validate_reg_c::<N>()?;
+
+        let mut state = input;
+        state[c] = self.input_a;
+        Ok(state)
+    }
+
+    pub fn gtir<const N: usize>(&self, input: State<W, N>) -> Result<State<W, N>, Error> {
+        let b = self.validate_reg_b::<N>()?;
+        let c = self.validate_reg_c::<N>()?;
+
+        let mut state = input;
+        state[c] = Self::flag(self.input_a > state[b]);
+        Ok(state)
+    }
+
+    pub fn gtri<const N: usize>(&self, input: State<W, N>) -> Result<State<W, N>, Error> {
+        let a = self.validate_reg_a::<N>()?;
+        let c = self.validate_reg_c::<N>()?;
+
+        let mut state = input;
+        state[c] = Self::flag(state[a] > self.input_b);
+        Ok(state)
+    }
+
+    pub fn gtrr<const N: usize>(&self, input: State<W, N>) -> Result<State<W, N>, Error> {
+        let a = self.validate_reg_a::<N>()?;
+        let b = self.validate_reg_b::<N>()?;
+        let c = self.validate_reg_c::<N>()?;
+
+        let mut state = input;
+        state[c] = Self::flag(state[a] > state[b]);
+        Ok(state)
+    }
+
+    pub fn eqir<const N: usize>(&self, input: State<W, N>) -> Result<State<W, N>, Error> {
+        let b = self.validate_reg_b::<N>()?;
+        let c = self.validate_reg_c::<N>()?;
+
+        let mut state = input;
+        state[c] = Self::flag(self.input_a == state[b]);
+        Ok(state)
+    }
+
+    pub fn eqri<const N: usize>(&self, input: State<W, N>) -> Result<State<W, N>, Error> {
+        let a = self.validate_reg_a::<N>()?;
+        let c = self.validate_reg_c::<N>()?;
+
+        let mut state = input;
+        state[c] = Self::flag(state[a] == self.input_b);
+        Ok(state)
+    }
+
+    pub fn eqrr<const N: usize>(&self, input: State<W, N>) -> Result<State<W, N>, Error> {
+        let a = self.validate_reg_a::<N>()?;
+        let b = self.validate_reg_b::<N>()?;
+        let c = self.validate_reg_c::<N>()?;
+
+        let mut state = input;
+        state[c] = Self::flag(state[a] == state[b]);
+        Ok(state)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parser() {
+        let instr = Instruction::<u32>::from_string("addi 5 16 5");
+        assert_eq!(
+            instr,
+            Instruction {
+                opcode: 1,
+                input_a: 5,
+                input_b: 16,
+                output_c: 5
+            }
+        );
+
+        let (_, instr) = Instruction::<u64>::parser_numeric("\n9 2 1 2").unwrap();
+        assert_eq!(
+            instr,
+            Instruction {
+                opcode: 9,
+                input_a: 2,
+                input_b: 1,
+                output_c: 2
+            }
+        );
+
+        assert!(Instruction::<u32>::parser("nope 1 2 3").is_err());
+    }
+
+    #[test]
+    fn test_dispatch_as() {
+        let instr = Instruction::<u32>::from_string("addi 2 1 2");
+        let before = State([3, 2, 1, 1]);
+        assert_eq!(instr.dispatch_as(before, 2), Ok(State([3, 2, 2, 1]))); // mulr
+        assert_eq!(instr.dispatch_as(before, 1), Ok(State([3, 2, 2, 1]))); // addi
+        assert_eq!(instr.dispatch_as(before, 9), Ok(State([3, 2, 2, 1]))); // seti
+        assert_eq!(instr.dispatch_as(before, 0), Ok(State([3, 2, 3, 1]))); // addr
+        assert_eq!(instr.dispatch_as(before, 16), Err(Error::Functioncode));
+    }
+
+    #[test]
+    fn test_opcodes() {
+        let before: State<u32, 4> = State([6, 3, 0, 0]);
+        let run = |name: &str, a: u32, b: u32| {
+            let instr = Instruction::from_string(&format!("{} {} {} 3", name, a, b));
+            instr.dispatch(before).unwrap()[3]
+        };
+
+        assert_eq!(run("addr", 0, 1), 9);
+        assert_eq!(run("addi", 0, 7), 13);
+        assert_eq!(run("mulr", 0, 1), 18);
+        assert_eq!(run("muli", 0, 7), 42);
+        assert_eq!(run("banr", 0, 1), 2);
+        assert_eq!(run("bani", 0, 12), 4);
+        assert_eq!(run("borr", 0, 1), 7);
+        assert_eq!(run("bori", 0, 8), 14);
+        assert_eq!(run("setr", 1, 99), 3);
+        assert_eq!(run("seti", 99, 99), 99);
+        assert_eq!(run("gtir", 7, 0), 1);
+        assert_eq!(run("gtri", 0, 6), 0);
+        assert_eq!(run("gtrr", 0, 1), 1);
+        assert_eq!(run("eqir", 3, 1), 1);
+        assert_eq!(run("eqri", 0, 5), 0);
+        assert_eq!(run("eqrr", 2, 3), 1);
+    }
+
+    #[test]
+    fn test_validate() {
+        let before: State<u32, 4> = State([0; 4]);
+        let instr = Instruction::<u32>::from_string("addr 4 0 0");
+        assert_eq!(instr.dispatch(before), Err(Error::InputA));
+        let instr = Instruction::<u32>::from_string("addr 0 4 0");
+        assert_eq!(instr.dispatch(before), Err(Error::InputB));
+        let instr = Instruction::<u32>::from_string("addi 0 400 4");
+        assert_eq!(instr.dispatch(before), Err(Error::OutputC));
+        let instr = Instruction::<u32>::from_string("seti 400 400 0");
+        assert_eq!(instr.dispatch(before), Ok(State([400, 0, 0, 0])));
+
+        let mut instr = Instruction::<u32>::from_string("seti 0 0 0");
+        instr.opcode = 16;
+        assert_eq!(instr.dispatch(before), Err(Error::Opcode));
+    }
+}
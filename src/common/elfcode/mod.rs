@@ -0,0 +1,15 @@
+mod assembler;
+mod cfg;
+mod compiler;
+mod computer;
+mod debugger;
+mod disasm;
+mod instruction;
+mod optimizer;
+mod state;
+
+pub use computer::*;
+pub use debugger::*;
+pub use instruction::*;
+pub use optimizer::*;
+pub use state::*;
@@ -0,0 +1,497 @@
+use super::{ChronalComputer, Instruction, State, Word};
+use std::collections::BTreeMap;
+
+#[derive(Clone, Copy)]
+enum Operand<W> {
+    Reg(char), // A register, the same name always refers to the same register and different names to different ones
+    Value(W),  // An exact immediate value
+    Capture(char), // Any immediate value, remembered under this name
+    Ignored,
+}
+
+use Operand::{Capture, Ignored, Reg, Value};
+
+struct Matcher<'a, W> {
+    program: &'a [Instruction<W>],
+    regs: BTreeMap<char, usize>,
+    values: BTreeMap<char, W>,
+}
+
+impl<'a, W: Word> Matcher<'a, W> {
+    fn new(program: &'a [Instruction<W>], ip_reg: usize) -> Self {
+        let mut regs = BTreeMap::new();
+        regs.insert('i', ip_reg); // 'i' is always the IP
+        Self {
+            program,
+            regs,
+            values: BTreeMap::new(),
+        }
+    }
+
+    fn operand(&mut self, operand: Operand<W>, value: W) -> bool {
+        match operand {
+            Reg(name) => {
+                let reg = match value.to_usize() {
+                    Some(r) => r,
+                    None => return false,
+                };
+                match self.regs.get(&name) {
+                    Some(&bound) => bound == reg,
+                    None if self.regs.values().any(|&r| r == reg) => false,
+                    None => {
+                        self.regs.insert(name, reg);
+                        true
+                    }
+                }
+            }
+            Value(v) => value == v,
+            Capture(name) => *self.values.entry(name).or_insert(value) == value,
+            Ignored => true,
+        }
+    }
+
+    fn matches(
+        &mut self,
+        line: usize,
+        name: &str,
+        a: Operand<W>,
+        b: Operand<W>,
+        c: Operand<W>,
+    ) -> bool {
+        let instr = match self.program.get(line) {
+            Some(instr) if instr.name() == name => *instr,
+            _ => return false,
+        };
+
+        // Commutative instructions may have their inputs either way around
+        let commutative = matches!(name, "addr" | "mulr" | "banr" | "borr" | "eqrr");
+        let saved = (self.regs.clone(), self.values.clone());
+        if self.operand(a, instr.input_a)
+            && self.operand(b, instr.input_b)
+            && self.operand(c, instr.output_c)
+        {
+            return true;
+        }
+
+        self.regs = saved.0.clone();
+        self.values = saved.1.clone();
+        if commutative == true
+            && self.operand(a, instr.input_b)
+            && self.operand(b, instr.input_a)
+            && self.operand(c, instr.output_c)
+        {
+            return true;
+        }
+
+        self.regs = saved.0;
+        self.values = saved.1;
+        false
+    }
+
+    fn reg(&self, name: char) -> usize {
+        self.regs[&name]
+    }
+
+    fn value(&self, name: char) -> W {
+        self.values[&name]
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FastPath<W> {
+    // Adds up every divisor of d that is at least b, by trying every b * c. The outer loop of day 19.
+    DivisorSum {
+        a: usize,
+        b: usize,
+        c: usize,
+        d: usize,
+        t: usize,
+        ip_reg: usize,
+        exit: usize,
+    },
+    // Adds b to a if b * c == d for any c from c up to d. The inner loop of day 19.
+    DivisorCheck {
+        a: usize,
+        b: usize,
+        c: usize,
+        d: usize,
+        t: usize,
+        ip_reg: usize,
+        exit: usize,
+    },
+    // Counts f up until (f + 1) * k > e, which is dividing e by k. Found in day 21.
+    DivideLoop {
+        b: usize,
+        e: usize,
+        f: usize,
+        k: W,
+        ip_reg: usize,
+        exit: usize,
+    },
+}
+
+impl<W: Word> FastPath<W> {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::DivisorSum { .. } => "divisor sum",
+            Self::DivisorCheck { .. } => "divisor check",
+            Self::DivideLoop { .. } => "divide loop",
+        }
+    }
+
+    fn find(program: &[Instruction<W>], ip_reg: usize, line: usize) -> Option<Self> {
+        Self::find_divisor_sum(program, ip_reg, line)
+            .or_else(|| Self::find_divisor_check(program, ip_reg, line))
+            .or_else(|| Self::find_divide_loop(program, ip_reg, line))
+    }
+
+    fn jump_to(line: usize) -> Option<Operand<W>> {
+        // A seti into the IP lands on the line after its value
+        Some(Value(W::from(line.checked_sub(1)?)?))
+    }
+
+    fn match_divisor_check(m: &mut Matcher<W>, l: usize) -> Option<()> {
+        //  l+0  mulr b c t      t = b * c
+        //  l+1  eqrr t d t      t = (t == d)
+        //  l+2  addr t ip ip    ip += t
+        //  l+3  addi ip 1 ip    ip += 1
+        //  l+4  addr b a a      a += b
+        //  l+5  addi c 1 c      c += 1
+        //  l+6  gtrr c d t      t = (c > d)
+        //  l+7  addr ip t ip    ip += t
+        //  l+8  seti l-1 _ ip   ip = l-1
+        let found = m.matches(l, "mulr", Reg('b'), Reg('c'), Reg('t'))
+            && m.matches(l + 1, "eqrr", Reg('t'), Reg('d'), Reg('t'))
+            && m.matches(l + 2, "addr", Reg('t'), Reg('i'), Reg('i'))
+            && m.matches(l + 3, "addi", Reg('i'), Value(W::one()), Reg('i'))
+            && m.matches(l + 4, "addr", Reg('b'), Reg('a'), Reg('a'))
+            && m.matches(l + 5, "addi", Reg('c'), Value(W::one()), Reg('c'))
+            && m.matches(l + 6, "gtrr", Reg('c'), Reg('d'), Reg('t'))
+            && m.matches(l + 7, "addr", Reg('i'), Reg('t'), Reg('i'))
+            && m.matches(l + 8, "seti", Self::jump_to(l)?, Ignored, Reg('i'));
+        if found == true {
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    fn find_divisor_check(program: &[Instruction<W>], ip_reg: usize, line: usize) -> Option<Self> {
+        let mut m = Matcher::new(program, ip_reg);
+        Self::match_divisor_check(&mut m, line)?;
+        Some(Self::DivisorCheck {
+            a: m.reg('a'),
+            b: m.reg('b'),
+            c: m.reg('c'),
+            d: m.reg('d'),
+            t: m.reg('t'),
+            ip_reg,
+            exit: line + 9,
+        })
+    }
+
+    fn find_divisor_sum(program: &[Instruction<W>], ip_reg: usize, line: usize) -> Option<Self> {
+        //  l+0   seti 1 _ c
+        //  l+1   the divisor check loop
+        //  l+10  addi b 1 b      b += 1
+        //  l+11  gtrr b d t      t = (b > d)
+        //  l+12  addr t ip ip    ip += t
+        //  l+13  seti l-1 _ ip   ip = l-1
+        let mut m = Matcher::new(program, ip_reg);
+        let found = m.matches(line, "seti", Value(W::one()), Ignored, Reg('c'))
+            && Self::match_divisor_check(&mut m, line + 1).is_some()
+            && m.matches(line + 10, "addi", Reg('b'), Value(W::one()), Reg('b'))
+            && m.matches(line + 11, "gtrr", Reg('b'), Reg('d'), Reg('t'))
+            && m.matches(line + 12, "addr", Reg('t'), Reg('i'), Reg('i'))
+            && m.matches(line + 13, "seti", Self::jump_to(line)?, Ignored, Reg('i'));
+        if found == false {
+            return None;
+        }
+
+        Some(Self::DivisorSum {
+            a: m.reg('a'),
+            b: m.reg('b'),
+            c: m.reg('c'),
+            d: m.reg('d'),
+            t: m.reg('t'),
+            ip_reg,
+            exit: line + 14,
+        })
+    }
+
+    fn find_divide_loop(program: &[Instruction<W>], ip_reg: usize, line: usize) -> Option<Self> {
+        //  l+0  addi f 1 b      b = f + 1
+        //  l+1  muli b k b      b *= k
+        //  l+2  gtrr b e b      b = (b > e)
+        //  l+3  addr b ip ip    ip += b
+        //  l+4  addi ip 1 ip    ip += 1
+        //  l+5  seti x _ ip     ip = x
+        //  l+6  addi f 1 f      f += 1
+        //  l+7  seti l-1 _ ip   ip = l-1
+        let mut m = Matcher::new(program, ip_reg);
+        let found = m.matches(line, "addi", Reg('f'), Value(W::one()), Reg('b'))
+            && m.matches(line + 1, "muli", Reg('b'), Capture('k'), Reg('b'))
+            && m.matches(line + 2, "gtrr", Reg('b'), Reg('e'), Reg('b'))
+            && m.matches(line + 3, "addr", Reg('b'), Reg('i'), Reg('i'))
+            && m.matches(line + 4, "addi", Reg('i'), Value(W::one()), Reg('i'))
+            && m.matches(line + 5, "seti", Capture('x'), Ignored, Reg('i'))
+            && m.matches(line + 6, "addi", Reg('f'), Value(W::one()), Reg('f'))
+            && m.matches(line + 7, "seti", Self::jump_to(line)?, Ignored, Reg('i'));
+        if found == false || m.value('k') == W::zero() {
+            return None; // A zero k never exits
+        }
+
+        Some(Self::DivideLoop {
+            b: m.reg('b'),
+            e: m.reg('e'),
+            f: m.reg('f'),
+            k: m.value('k'),
+            ip_reg,
+            exit: m.value('x').to_usize()?.checked_add(1)?,
+        })
+    }
+
+    fn divisor_count(b: W, low: W, high: W, d: W) -> W {
+        // How many c in low..=high have b * c == d
+        if b == W::zero() {
+            if d == W::zero() {
+                high - low + W::one()
+            } else {
+                W::zero()
+            }
+        } else if d % b == W::zero() && low <= d / b && d / b <= high {
+            W::one()
+        } else {
+            W::zero()
+        }
+    }
+
+    fn divisor_sum(d: W, low: W) -> W {
+        // Sum of the divisors of d which are at least low
+        let mut sum = W::zero();
+        let mut i = W::one();
+        while i.checked_mul(&i).is_some_and(|sq| sq <= d) {
+            if d % i == W::zero() {
+                let j = d / i;
+                if i >= low {
+                    sum = sum + i;
+                }
+                if j != i && j >= low {
+                    sum = sum + j;
+                }
+            }
+            i = i + W::one();
+        }
+        sum
+    }
+
+    // Runs the whole idiom, leaving the registers as if each instruction had run. Returns the new IP.
+    pub fn apply<const N: usize>(&self, state: &mut State<W, N>) -> usize {
+        match *self {
+            Self::DivisorSum {
+                a,
+                b,
+                c,
+                d,
+                t,
+                ip_reg,
+                exit,
+            } => {
+                let high = state[b].max(state[d]);
+                state[a] = state[a] + Self::divisor_sum(state[d], state[b]);
+                state[b] = high + W::one();
+                state[c] = state[d].max(W::one()) + W::one();
+                state[t] = W::one();
+                state[ip_reg] = W::from(exit - 1).unwrap();
+                exit
+            }
+            Self::DivisorCheck {
+                a,
+                b,
+                c,
+                d,
+                t,
+                ip_reg,
+                exit,
+            } => {
+                let high = state[c].max(state[d]);
+                let count = Self::divisor_count(state[b], state[c], high, state[d]);
+                state[a] = state[a] + state[b] * count;
+                state[c] = high + W::one();
+                state[t] = W::one();
+                state[ip_reg] = W::from(exit - 1).unwrap();
+                exit
+            }
+            Self::DivideLoop {
+                b,
+                e,
+                f,
+                k,
+                ip_reg,
+                exit,
+            } => {
+                state[f] = state[f].max(state[e] / k);
+                state[b] = W::one();
+                state[ip_reg] = W::from(exit - 1).unwrap();
+                exit
+            }
+        }
+    }
+}
+
+impl<W: Word, const N: usize> ChronalComputer<W, N> {
+    pub fn optimize(&mut self) -> Vec<usize> {
+        // Looks for known idioms and runs them natively whenever the IP lands on their first line.
+        // Returns those lines. The program shouldn't be changed after this.
+        let ip_reg = match self.ip_reg {
+            Some(reg) => reg,
+            None => return Vec::new(),
+        };
+
+        self.fast_paths = (0..self.program.len())
+            .map(|line| FastPath::find(&self.program, ip_reg, line))
+            .collect();
+        if self.is_compiled() == true {
+            self.compile().unwrap();
+        }
+        (0..self.program.len())
+            .filter(|&line| self.fast_paths[line].is_some())
+            .collect()
+    }
+
+    pub fn fast_path_at(&self, line: usize) -> Option<&FastPath<W>> {
+        self.fast_paths.get(line).and_then(|f| f.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const DIVISOR_SUM: &str = "\
+#ip 5
+seti 30 0 3
+seti 1 0 1
+seti 1 0 2
+mulr 1 2 4
+eqrr 4 3 4
+addr 4 5 5
+addi 5 1 5
+addr 1 0 0
+addi 2 1 2
+gtrr 2 3 4
+addr 5 4 5
+seti 2 0 5
+addi 1 1 1
+gtrr 1 3 4
+addr 4 5 5
+seti 1 0 5";
+
+    const DIVIDE_LOOP: &str = "\
+#ip 2
+seti 1000 0 4
+seti 0 0 5
+addi 5 1 1
+muli 1 256 1
+gtrr 1 4 1
+addr 1 2 2
+addi 2 1 2
+seti 9 0 2
+addi 5 1 5
+seti 1 0 2
+setr 5 0 4";
+
+    fn compare(input: &str, setup: impl Fn(&mut ChronalComputer<u32, 6>)) -> usize {
+        // Runs the program plainly and optimized, they must finish identically. Returns the number of plain steps.
+        let mut plain = ChronalComputer::<u32, 6>::from_string(input);
+        setup(&mut plain);
+        let mut steps = 0;
+        while plain.is_halted() == false {
+            plain.step().unwrap();
+            steps += 1;
+        }
+
+        let mut fast = ChronalComputer::<u32, 6>::from_string(input);
+        setup(&mut fast);
+        assert!(fast.optimize().is_empty() == false);
+        fast.run().unwrap();
+
+        assert_eq!(fast.state, plain.state);
+        assert_eq!(fast.ip, plain.ip);
+        steps
+    }
+
+    #[test]
+    fn test_find() {
+        let mut chronal = ChronalComputer::<u32, 6>::from_string(DIVISOR_SUM);
+        assert_eq!(chronal.optimize(), vec![2, 3]);
+        assert_eq!(
+            chronal.fast_paths[2],
+            Some(FastPath::DivisorSum {
+                a: 0,
+                b: 1,
+                c: 2,
+                d: 3,
+                t: 4,
+                ip_reg: 5,
+                exit: 16
+            })
+        );
+
+        let mut chronal = ChronalComputer::<u32, 6>::from_string(DIVIDE_LOOP);
+        assert_eq!(chronal.optimize(), vec![2]);
+
+        // Without an IP register there are no jumps and so no loops
+        let mut chronal = ChronalComputer::<u32, 6>::from_string("seti 1 0 2");
+        assert_eq!(chronal.optimize(), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn test_divisor_sum() {
+        for d in 0..=40 {
+            for b in 0..=3 {
+                compare(DIVISOR_SUM, |chronal| {
+                    chronal.program[0].input_a = d;
+                    chronal.program[1].input_a = b;
+                });
+            }
+        }
+
+        let mut chronal = ChronalComputer::<u32, 6>::from_string(DIVISOR_SUM);
+        chronal.optimize();
+        chronal.run().unwrap();
+        assert_eq!(chronal.state[0], 1 + 2 + 3 + 5 + 6 + 10 + 15 + 30);
+    }
+
+    #[test]
+    fn test_divisor_check() {
+        // Stepping b by 2 is a different outer loop, but the inner loop is still recognised
+        let input = DIVISOR_SUM.replace("addi 1 1 1", "addi 1 2 1");
+        let mut chronal = ChronalComputer::<u32, 6>::from_string(&input);
+        assert_eq!(chronal.optimize(), vec![3]);
+
+        for d in 0..=40 {
+            for c in 0..=3 {
+                compare(&input, |chronal| {
+                    chronal.program[0].input_a = d;
+                    chronal.program[2].input_a = c;
+                });
+            }
+        }
+    }
+
+    #[test]
+    fn test_divide_loop() {
+        for e in (0..2000).step_by(7) {
+            for f in 0..=5 {
+                compare(DIVIDE_LOOP, |chronal| {
+                    chronal.program[0].input_a = e;
+                    chronal.program[1].input_a = f;
+                });
+            }
+        }
+
+        let steps = compare(DIVIDE_LOOP, |_| {});
+        assert_eq!(steps, 2 + 3 * 7 + 5 + 1);
+    }
+}
@@ -0,0 +1,74 @@
+use nom::{
+    bytes::complete::tag,
+    character::complete::{char, digit1, multispace0},
+    combinator::{map_res, verify},
+    multi::separated_list1,
+    sequence::{delimited, pair},
+    IResult,
+};
+use num::{PrimInt, Unsigned};
+use std::convert::TryInto;
+use std::fmt;
+use std::hash::Hash;
+use std::ops::{Index, IndexMut};
+use std::str::FromStr;
+
+// The value type held by each register. Day 16 and 19 programs fit in a u32 while day 21 needs a u64.
+pub trait Word: PrimInt + Unsigned + Hash + fmt::Debug + fmt::Display + FromStr + 'static {}
+
+impl<T> Word for T where T: PrimInt + Unsigned + Hash + fmt::Debug + fmt::Display + FromStr + 'static
+{}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct State<W, const N: usize>(pub [W; N]);
+
+impl<W: Word, const N: usize> State<W, N> {
+    pub fn new() -> Self {
+        Self([W::zero(); N])
+    }
+
+    pub fn parser(input: &str) -> IResult<&str, Self> {
+        // Accepts a bracketed list with exactly one value per register, e.g. [3, 2, 1, 1]
+        let (input, values) = delimited(
+            pair(multispace0, char('[')),
+            verify(
+                separated_list1(tag(", "), map_res(digit1, |x: &str| x.parse::<W>())),
+                |values: &Vec<W>| values.len() == N,
+            ),
+            char(']'),
+        )(input)?;
+
+        Ok((input, Self(values.try_into().unwrap())))
+    }
+}
+
+impl<W, const N: usize> Index<usize> for State<W, N> {
+    type Output = W;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.0[index]
+    }
+}
+
+impl<W, const N: usize> IndexMut<usize> for State<W, N> {
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        &mut self.0[index]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parser() {
+        let (_, state) = State::<u32, 4>::parser("[3, 2, 1, 1]").unwrap();
+        assert_eq!(state, State([3, 2, 1, 1]));
+
+        let (_, state) = State::<u64, 6>::parser("\n[0, 1, 2, 3, 4, 5]").unwrap();
+        assert_eq!(state, State([0, 1, 2, 3, 4, 5]));
+
+        assert!(State::<u32, 4>::parser("[3, 2, 1]").is_err());
+        assert!(State::<u32, 4>::parser("[3, 2, 1, 1, 0]").is_err());
+    }
+}
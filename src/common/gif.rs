@@ -0,0 +1,297 @@
+use crate::common::Image;
+use crate::common::Palette;
+use std::collections::HashMap;
+
+// An animated GIF, built up a frame at a time. Only the part of each frame that changed is
+// stored, so a simulation that changes a few cells at a time stays small.
+
+const MAX_CODE: u16 = 4095;
+
+pub struct Gif {
+    min_code_size: u8,
+    screen: Image, // What a viewer shows after the frames so far
+    bytes: Vec<u8>,
+    frames: usize,
+    last_delay_at: Option<usize>, // Where the previous frame's delay is, so it can be extended
+}
+
+impl Gif {
+    pub fn new(palette: &Palette) -> Self {
+        // The colour table must have a power of two entries, at least two
+        let colours = palette.colours();
+        let table_bits = (1..=8).find(|&b| 1 << b >= colours.len()).unwrap();
+        let mut bytes = b"GIF89a".to_vec();
+        bytes.extend_from_slice(&[0, 0, 0, 0]); // Size, filled in as frames are added
+        bytes.extend_from_slice(&[0xf0 | (table_bits - 1), 0, 0]);
+        for i in 0..1 << table_bits {
+            bytes.extend_from_slice(colours.get(i).unwrap_or(&[0, 0, 0]));
+        }
+
+        // Loop forever
+        bytes.extend_from_slice(b"\x21\xff\x0bNETSCAPE2.0\x03\x01\0\0\0");
+
+        Self {
+            min_code_size: table_bits.max(2),
+            screen: Image::new(0, 0),
+            bytes,
+            frames: 0,
+            last_delay_at: None,
+        }
+    }
+
+    pub fn add_frame(&mut self, image: &Image, delay: u16) {
+        // Delay is in hundredths of a second. Frames are drawn at the top left, and the animation
+        // grows to fit the biggest. Anything a frame doesn't cover is background.
+        let width = self.screen.width().max(image.width());
+        let height = self.screen.height().max(image.height());
+        assert!(
+            width <= u16::MAX as usize && height <= u16::MAX as usize,
+            "Frame is too big"
+        );
+        let grew = (width, height) != (self.screen.width(), self.screen.height());
+        if grew == true {
+            self.bytes[6..8].copy_from_slice(&(width as u16).to_le_bytes());
+            self.bytes[8..10].copy_from_slice(&(height as u16).to_le_bytes());
+        }
+
+        let frame = if (image.width(), image.height()) == (width, height) {
+            image.clone()
+        } else {
+            Image::from_fn(width, height, |x, y| image.get(x, y))
+        };
+        let changed = if grew == true {
+            Some((0, 0, width - 1, height - 1))
+        } else {
+            frame.difference(&self.screen)
+        };
+        self.frames += 1;
+
+        let (left, top, right, bottom) = match changed {
+            Some(area) => area,
+            None => {
+                // Nothing to draw, just show the previous frame for longer
+                if let Some(at) = self.last_delay_at {
+                    let total = u16::from_le_bytes([self.bytes[at], self.bytes[at + 1]]);
+                    let total = total.saturating_add(delay).to_le_bytes();
+                    self.bytes[at..at + 2].copy_from_slice(&total);
+                }
+                return;
+            }
+        };
+
+        // Graphic control extension, leaving each frame in place for the next to be drawn over
+        self.bytes.extend_from_slice(&[0x21, 0xf9, 0x04, 0x04]);
+        self.last_delay_at = Some(self.bytes.len());
+        self.bytes.extend_from_slice(&delay.to_le_bytes());
+        self.bytes.extend_from_slice(&[0, 0]);
+
+        // Image descriptor for just the area that changed
+        self.bytes.push(0x2c);
+        for value in [left, top, right - left + 1, bottom - top + 1] {
+            self.bytes.extend_from_slice(&(value as u16).to_le_bytes());
+        }
+        self.bytes.push(0);
+
+        let pixels: Vec<u8> = (top..=bottom)
+            .flat_map(|y| (left..=right).map(move |x| (x, y)))
+            .map(|(x, y)| frame.get(x, y))
+            .collect();
+        self.bytes.push(self.min_code_size);
+        for block in lzw(&pixels, self.min_code_size).chunks(255) {
+            self.bytes.push(block.len() as u8);
+            self.bytes.extend_from_slice(block);
+        }
+        self.bytes.push(0);
+
+        self.screen = frame;
+    }
+
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    pub fn finish(mut self) -> Vec<u8> {
+        self.bytes.push(0x3b);
+        self.bytes
+    }
+}
+
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    bits: u8,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, width: u8) {
+        // Least significant bit first
+        self.buffer |= (code as u32) << self.bits;
+        self.bits += width;
+        while self.bits >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+fn lzw(indexes: &[u8], min_code_size: u8) -> Vec<u8> {
+    // Codes start one bit wider than the indexes and grow as the table fills. When it's full it's
+    // cleared and started again.
+    let clear = 1 << min_code_size;
+    let end = clear + 1;
+    let mut writer = BitWriter {
+        bytes: Vec::new(),
+        buffer: 0,
+        bits: 0,
+    };
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut width = min_code_size + 1;
+    let mut highest = end;
+
+    writer.write(clear, width);
+    let mut indexes = indexes.iter();
+    let mut current = match indexes.next() {
+        Some(&i) => i as u16,
+        None => {
+            writer.write(end, width);
+            return writer.finish();
+        }
+    };
+    for &i in indexes {
+        if let Some(&code) = table.get(&(current, i)) {
+            current = code;
+            continue;
+        }
+
+        writer.write(current, width);
+        highest += 1;
+        if highest == 1 << width {
+            width += 1;
+        }
+        if highest == MAX_CODE {
+            writer.write(clear, width);
+            table.clear();
+            width = min_code_size + 1;
+            highest = end;
+        } else {
+            table.insert((current, i), highest);
+        }
+        current = i as u16;
+    }
+
+    // The decoder adds to its table after the last code too, so the end code must allow for that
+    writer.write(current, width);
+    highest += 1;
+    if highest == 1 << width {
+        width += 1;
+    }
+    if highest == MAX_CODE {
+        writer.write(clear, width);
+        width = min_code_size + 1;
+    }
+    writer.write(end, width);
+    writer.finish()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn decode(bytes: &[u8], min_code_size: u8) -> Vec<u8> {
+        let clear = 1 << min_code_size;
+        let end = clear + 1;
+        let mut bits = bytes
+            .iter()
+            .flat_map(|&byte| (0..8).map(move |bit| (byte >> bit) & 1));
+        let mut read = |width: u8| -> usize {
+            (0..width).fold(0, |code, bit| code | (bits.next().unwrap() as usize) << bit)
+        };
+
+        let mut output = Vec::new();
+        let mut table: Vec<Vec<u8>> = Vec::new();
+        let mut width = min_code_size + 1;
+        let mut previous: Option<Vec<u8>> = None;
+        loop {
+            let code = read(width);
+            if code == clear {
+                table = (0..clear).map(|i| vec![i as u8]).collect();
+                table.extend([Vec::new(), Vec::new()]);
+                width = min_code_size + 1;
+                previous = None;
+                continue;
+            }
+            if code == end {
+                return output;
+            }
+            let entry = match (table.get(code), &previous) {
+                (Some(entry), _) => entry.clone(),
+                (None, Some(p)) => [p.clone(), vec![p[0]]].concat(),
+                (None, None) => panic!("Unknown code {}", code),
+            };
+            output.extend(&entry);
+            if let Some(p) = previous {
+                table.push([p, vec![entry[0]]].concat());
+                if table.len() == 1 << width && width < 12 {
+                    width += 1;
+                }
+            }
+            previous = Some(entry);
+        }
+    }
+
+    #[test]
+    fn test_lzw() {
+        // Long enough for the table to fill and be cleared a few times
+        let mut seed: u32 = 1;
+        let indexes: Vec<u8> = (0..50_000)
+            .map(|_| {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (seed >> 16) as u8 % 4
+            })
+            .collect();
+        assert_eq!(decode(&lzw(&indexes, 2), 2), indexes);
+
+        let repeated = vec![3; 10_000];
+        assert_eq!(decode(&lzw(&repeated, 3), 3), repeated);
+        assert_eq!(decode(&lzw(&[], 2), 2), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn test_frames() {
+        let palette = Palette::new([0, 0, 0]).with('#', [255, 255, 255]);
+        let mut gif = Gif::new(&palette);
+        gif.add_frame(&Image::from_text("#..\n...", &palette, 1), 10);
+        gif.add_frame(&Image::from_text("#..\n...", &palette, 1), 10);
+        gif.add_frame(&Image::from_text("#..\n.#.", &palette, 1), 10);
+        gif.add_frame(&Image::from_text("#...\n.#.", &palette, 1), 10);
+        assert_eq!(gif.frames(), 4);
+        let bytes = gif.finish();
+
+        // Three frames stored. The first is shown for twice as long, the second only covers the one
+        // cell that changed, and the third is wider so is drawn in full.
+        let controls: Vec<usize> = (0..bytes.len() - 1)
+            .filter(|&i| bytes[i..].starts_with(&[0x21, 0xf9]))
+            .collect();
+        assert_eq!(controls.len(), 3);
+        assert_eq!(bytes[controls[0] + 4], 20);
+        assert_eq!(bytes[controls[1] + 4], 10);
+        assert_eq!(
+            &bytes[controls[1] + 8..controls[1] + 18],
+            &[0x2c, 1, 0, 1, 0, 1, 0, 1, 0, 0]
+        );
+        assert_eq!(
+            &bytes[controls[2] + 8..controls[2] + 18],
+            &[0x2c, 0, 0, 0, 0, 4, 0, 2, 0, 0]
+        );
+        assert!(bytes.starts_with(b"GIF89a\x04\0\x02\0\xf0"));
+        assert!(bytes.ends_with(&[0, 0x3b]));
+    }
+}
@@ -0,0 +1,448 @@
+use crate::common::Point;
+use std::collections::BTreeMap;
+use std::fmt;
+
+// A tile parsed from one character of puzzle input. None means there's nothing at that point, which
+// only a sparse grid can hold.
+pub trait FromChar: Sized {
+    fn from_char(c: char) -> Option<Self>;
+}
+
+pub trait ToChar {
+    fn to_char(&self) -> char;
+}
+
+impl FromChar for char {
+    fn from_char(c: char) -> Option<Self> {
+        Some(c)
+    }
+}
+
+impl ToChar for char {
+    fn to_char(&self) -> char {
+        *self
+    }
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+enum Backing<T> {
+    // Every point within the bounds has a value, stored row by row
+    Dense {
+        min: Point,
+        width: usize,
+        height: usize,
+        cells: Vec<T>,
+    },
+    // Only points that have been set. Point ordering means this iterates in reading order too.
+    Sparse(BTreeMap<Point, T>),
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Grid<T> {
+    backing: Backing<T>,
+}
+
+impl<T> Grid<T> {
+    pub fn dense(min: Point, max: Point, fill: T) -> Self
+    where
+        T: Clone,
+    {
+        let width = (max.x - min.x + 1).max(0) as usize;
+        let height = (max.y - min.y + 1).max(0) as usize;
+        Self {
+            backing: Backing::Dense {
+                min,
+                width,
+                height,
+                cells: vec![fill; width * height],
+            },
+        }
+    }
+
+    pub fn sparse() -> Self {
+        Self {
+            backing: Backing::Sparse(BTreeMap::new()),
+        }
+    }
+
+    fn parse_chars(input: &str) -> impl Iterator<Item = (Point, char)> + '_ {
+        input
+            .trim_matches(|c| c == '\n' || c == '\r')
+            .lines()
+            .enumerate()
+            .flat_map(|(y, line)| {
+                line.chars().enumerate().map(move |(x, c)| {
+                    let p = Point {
+                        x: x as i32,
+                        y: y as i32,
+                    };
+                    (p, c)
+                })
+            })
+    }
+
+    pub fn from_string(input: &str) -> Self
+    where
+        T: FromChar,
+    {
+        // Builds a dense grid with the top left at (0, 0). Every line must be the same length. Blank
+        // lines around the picture are ignored, but leading spaces on a line are significant.
+        let mut cells = Vec::new();
+        let mut width = None;
+        let mut height = 0;
+        for line in input.trim_matches(|c| c == '\n' || c == '\r').lines() {
+            let row: Vec<T> = line
+                .chars()
+                .enumerate()
+                .map(|(x, c)| {
+                    let p = Point {
+                        x: x as i32,
+                        y: height as i32,
+                    };
+                    T::from_char(c).unwrap_or_else(|| panic!("No tile at {}", p))
+                })
+                .collect();
+            assert_eq!(
+                *width.get_or_insert(row.len()),
+                row.len(),
+                "Ragged grid at line {}",
+                height
+            );
+            cells.extend(row);
+            height += 1;
+        }
+
+        Self {
+            backing: Backing::Dense {
+                min: Point::new(),
+                width: width.unwrap_or(0),
+                height,
+                cells,
+            },
+        }
+    }
+
+    pub fn sparse_from_string(input: &str) -> Self
+    where
+        T: FromChar,
+    {
+        // Points where from_char gives None are left out
+        Self::parse_chars(input)
+            .filter_map(|(p, c)| T::from_char(c).map(|t| (p, t)))
+            .collect()
+    }
+
+    pub fn is_dense(&self) -> bool {
+        matches!(self.backing, Backing::Dense { .. })
+    }
+
+    fn index(&self, p: Point) -> Option<usize> {
+        // Where a point lives in a dense grid, if it's within the bounds
+        match &self.backing {
+            Backing::Dense {
+                min, width, height, ..
+            } => {
+                let (dx, dy) = (p.x - min.x, p.y - min.y);
+                if dx < 0 || dy < 0 || dx as usize >= *width || dy as usize >= *height {
+                    None
+                } else {
+                    Some(dy as usize * width + dx as usize)
+                }
+            }
+            Backing::Sparse(_) => None,
+        }
+    }
+
+    pub fn bounds(&self) -> Option<(Point, Point)> {
+        // The top left and bottom right corners, inclusive
+        match &self.backing {
+            Backing::Dense {
+                min, width, height, ..
+            } => {
+                if *width == 0 || *height == 0 {
+                    None
+                } else {
+                    Some((*min, *min + (*width as i32 - 1, *height as i32 - 1)))
+                }
+            }
+            Backing::Sparse(tiles) => Point::get_range(tiles.keys())
+                .map(|(x, y)| (Point { x: x.0, y: y.0 }, Point { x: x.1, y: y.1 })),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match &self.backing {
+            Backing::Dense { cells, .. } => cells.len(),
+            Backing::Sparse(tiles) => tiles.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn contains(&self, p: Point) -> bool {
+        self.get(p).is_some()
+    }
+
+    pub fn get(&self, p: Point) -> Option<&T> {
+        match &self.backing {
+            Backing::Dense { cells, .. } => self.index(p).map(|i| &cells[i]),
+            Backing::Sparse(tiles) => tiles.get(&p),
+        }
+    }
+
+    pub fn get_mut(&mut self, p: Point) -> Option<&mut T> {
+        let index = self.index(p);
+        match &mut self.backing {
+            Backing::Dense { cells, .. } => index.map(move |i| &mut cells[i]),
+            Backing::Sparse(tiles) => tiles.get_mut(&p),
+        }
+    }
+
+    pub fn get_or_insert_with(&mut self, p: Point, default: impl FnOnce() -> T) -> &mut T {
+        // A dense grid already has a value everywhere it can
+        let index = self.index(p);
+        match &mut self.backing {
+            Backing::Dense { cells, .. } => {
+                &mut cells[index.unwrap_or_else(|| panic!("{} is outside a dense grid", p))]
+            }
+            Backing::Sparse(tiles) => tiles.entry(p).or_insert_with(default),
+        }
+    }
+
+    pub fn insert(&mut self, p: Point, value: T) -> Option<T> {
+        // Returns the previous value. A dense grid can't grow, so the point must be in bounds.
+        let index = self.index(p);
+        match &mut self.backing {
+            Backing::Dense { cells, .. } => {
+                let i = index.unwrap_or_else(|| panic!("{} is outside a dense grid", p));
+                Some(std::mem::replace(&mut cells[i], value))
+            }
+            Backing::Sparse(tiles) => tiles.insert(p, value),
+        }
+    }
+
+    pub fn remove(&mut self, p: Point) -> Option<T> {
+        match &mut self.backing {
+            Backing::Dense { .. } => panic!("Can't remove {} from a dense grid", p),
+            Backing::Sparse(tiles) => tiles.remove(&p),
+        }
+    }
+
+    pub fn iter(&self) -> Box<dyn Iterator<Item = (Point, &T)> + '_> {
+        // In reading order, whichever the backing
+        match &self.backing {
+            Backing::Dense {
+                min, width, cells, ..
+            } => {
+                let (min, width) = (*min, *width);
+                Box::new(cells.iter().enumerate().map(move |(i, t)| {
+                    let p = min + ((i % width) as i32, (i / width) as i32);
+                    (p, t)
+                }))
+            }
+            Backing::Sparse(tiles) => Box::new(tiles.iter().map(|(&p, t)| (p, t))),
+        }
+    }
+
+    pub fn points(&self) -> impl Iterator<Item = Point> + '_ {
+        self.iter().map(|(p, _)| p)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &T> + '_ {
+        self.iter().map(|(_, t)| t)
+    }
+
+    pub fn orthogonals(&self, p: Point) -> impl Iterator<Item = (Point, &T)> + '_ {
+        // Neighbours that hold a value, in the same order as Point::orthogonals
+        p.orthogonals()
+            .into_iter()
+            .filter_map(move |n| self.get(n).map(|t| (n, t)))
+    }
+
+    pub fn adjacents(&self, p: Point) -> impl Iterator<Item = (Point, &T)> + '_ {
+        // Neighbours including diagonals that hold a value, in the same order as Point::adjacents
+        p.adjacents()
+            .into_iter()
+            .filter_map(move |n| self.get(n).map(|t| (n, t)))
+    }
+
+    pub fn map<U>(&self, mut f: impl FnMut(Point, &T) -> U) -> Grid<U> {
+        // A new grid with the same backing and points
+        let backing = match &self.backing {
+            Backing::Dense {
+                min, width, height, ..
+            } => Backing::Dense {
+                min: *min,
+                width: *width,
+                height: *height,
+                cells: self.iter().map(|(p, t)| f(p, t)).collect(),
+            },
+            Backing::Sparse(_) => Backing::Sparse(self.iter().map(|(p, t)| (p, f(p, t))).collect()),
+        };
+        Grid { backing }
+    }
+
+    pub fn render_within(
+        &self,
+        min: Point,
+        max: Point,
+        mut cell: impl FnMut(Point, Option<&T>) -> char,
+    ) -> String {
+        // One line per row, each ending in a newline
+        let mut output = String::new();
+        for y in min.y..=max.y {
+            for x in min.x..=max.x {
+                let p = Point { x, y };
+                output.push(cell(p, self.get(p)));
+            }
+            output.push('\n');
+        }
+        output
+    }
+
+    pub fn render(&self, cell: impl FnMut(Point, Option<&T>) -> char) -> String {
+        match self.bounds() {
+            Some((min, max)) => self.render_within(min, max, cell),
+            None => String::new(),
+        }
+    }
+}
+
+impl<T> std::iter::FromIterator<(Point, T)> for Grid<T> {
+    fn from_iter<I: IntoIterator<Item = (Point, T)>>(iter: I) -> Self {
+        Self {
+            backing: Backing::Sparse(iter.into_iter().collect()),
+        }
+    }
+}
+
+impl<T> Extend<(Point, T)> for Grid<T> {
+    fn extend<I: IntoIterator<Item = (Point, T)>>(&mut self, iter: I) {
+        for (p, t) in iter {
+            self.insert(p, t);
+        }
+    }
+}
+
+impl<T: ToChar> fmt::Display for Grid<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Points without a value are blank
+        let output = self.render(|_, t| t.map_or(' ', |t| t.to_char()));
+        write!(f, "{}", output)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+    enum Tile {
+        Open,
+        Wall,
+    }
+
+    impl FromChar for Tile {
+        fn from_char(c: char) -> Option<Self> {
+            match c {
+                '.' => Some(Self::Open),
+                '#' => Some(Self::Wall),
+                ' ' => None,
+                _ => panic!("Unknown tile: {}", c),
+            }
+        }
+    }
+
+    impl ToChar for Tile {
+        fn to_char(&self) -> char {
+            match self {
+                Self::Open => '.',
+                Self::Wall => '#',
+            }
+        }
+    }
+
+    #[test]
+    fn test_dense() {
+        let input = "
+###
+#..
+";
+        let mut grid: Grid<Tile> = Grid::from_string(input);
+        assert!(grid.is_dense());
+        assert_eq!(grid.len(), 6);
+        assert_eq!(
+            grid.bounds(),
+            Some((Point { x: 0, y: 0 }, Point { x: 2, y: 1 }))
+        );
+        assert_eq!(grid.get(Point { x: 1, y: 1 }), Some(&Tile::Open));
+        assert_eq!(grid.get(Point { x: 3, y: 1 }), None);
+        assert_eq!(grid.to_string(), "###\n#..\n");
+
+        assert_eq!(
+            grid.insert(Point { x: 2, y: 1 }, Tile::Wall),
+            Some(Tile::Open)
+        );
+        let walls: Vec<Point> = grid
+            .orthogonals(Point { x: 1, y: 1 })
+            .filter(|&(_, &t)| t == Tile::Wall)
+            .map(|(p, _)| p)
+            .collect();
+        assert_eq!(
+            walls,
+            vec![
+                Point { x: 1, y: 0 },
+                Point { x: 2, y: 1 },
+                Point { x: 0, y: 1 }
+            ]
+        );
+        assert_eq!(grid.adjacents(Point { x: 0, y: 0 }).count(), 3);
+
+        let flipped = grid.map(|_, &t| {
+            if t == Tile::Wall {
+                Tile::Open
+            } else {
+                Tile::Wall
+            }
+        });
+        assert_eq!(flipped.to_string(), "...\n.#.\n");
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_dense_ragged() {
+        let _grid: Grid<Tile> = Grid::from_string("###\n#.\n");
+    }
+
+    #[test]
+    fn test_sparse() {
+        let mut grid: Grid<Tile> = Grid::sparse_from_string("  #\n #.");
+        assert!(grid.is_dense() == false);
+        assert_eq!(grid.len(), 3);
+        assert_eq!(
+            grid.bounds(),
+            Some((Point { x: 1, y: 0 }, Point { x: 2, y: 1 }))
+        );
+        assert_eq!(grid.to_string(), " #\n#.\n");
+
+        grid.insert(Point { x: -1, y: 2 }, Tile::Open);
+        *grid.get_or_insert_with(Point { x: 0, y: 0 }, || Tile::Open) = Tile::Wall;
+        assert_eq!(grid.remove(Point { x: 2, y: 0 }), Some(Tile::Wall));
+        let points: Vec<Point> = grid.points().collect();
+        assert_eq!(
+            points,
+            vec![
+                Point { x: 0, y: 0 },
+                Point { x: 1, y: 1 },
+                Point { x: 2, y: 1 },
+                Point { x: -1, y: 2 }
+            ]
+        );
+        assert_eq!(
+            grid.render(|_, t| t.map_or('?', |t| t.to_char())),
+            "?#??\n??#.\n.???\n"
+        );
+    }
+}
@@ -0,0 +1,287 @@
+use std::collections::HashMap;
+
+// Turns the text a day draws itself with into a picture. Each character gets a colour from a
+// palette, and each cell becomes a square block of pixels. Pixels are stored as indexes into the
+// palette, which is what PNG and GIF both want.
+
+pub type Rgb = [u8; 3];
+
+#[derive(Clone, Debug)]
+pub struct Palette {
+    colours: Vec<Rgb>, // The first is the background, used for any character without a colour
+    ascii: [u8; 128],  // Quicker to look up than the map, which has the rest
+    indexes: HashMap<char, u8>,
+}
+
+impl Palette {
+    pub fn new(background: Rgb) -> Self {
+        Self {
+            colours: vec![background],
+            ascii: [0; 128],
+            indexes: HashMap::new(),
+        }
+    }
+
+    pub fn with(mut self, c: char, colour: Rgb) -> Self {
+        let existing = self.index(c);
+        if existing != 0 {
+            self.colours[existing as usize] = colour;
+            return self;
+        }
+
+        assert!(self.colours.len() < 256, "Palette is full");
+        let i = self.colours.len() as u8;
+        self.colours.push(colour);
+        if c.is_ascii() == true {
+            self.ascii[c as usize] = i;
+        } else {
+            self.indexes.insert(c, i);
+        }
+        self
+    }
+
+    pub fn with_overrides(self, spec: &str) -> Self {
+        // Comma separated, e.g. "~=1e90ff,#=8b4513"
+        spec.split(',')
+            .filter(|s| s.is_empty() == false)
+            .fold(self, |palette, entry| {
+                let mut chars = entry.chars();
+                let c = chars.next().unwrap();
+                let hex = chars.as_str().strip_prefix('=');
+                let colour = hex
+                    .filter(|hex| hex.len() == 6)
+                    .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                    .unwrap_or_else(|| panic!("Bad palette entry: {}", entry));
+                let [_, r, g, b] = colour.to_be_bytes();
+                palette.with(c, [r, g, b])
+            })
+    }
+
+    pub fn index(&self, c: char) -> u8 {
+        if c.is_ascii() == true {
+            self.ascii[c as usize]
+        } else {
+            self.indexes.get(&c).copied().unwrap_or(0)
+        }
+    }
+
+    pub fn colours(&self) -> &[Rgb] {
+        &self.colours
+    }
+}
+
+pub fn text_size(text: &str) -> (usize, usize) {
+    // Columns and rows, ignoring blank lines at the end
+    let lines = text.trim_end_matches('\n').lines();
+    lines.fold((0, 0), |(columns, rows), line| {
+        (columns.max(line.chars().count()), rows + 1)
+    })
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Image {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize) -> Self {
+        // All background
+        Self {
+            width,
+            height,
+            pixels: vec![0; width * height],
+        }
+    }
+
+    pub fn from_fn(width: usize, height: usize, mut pixel: impl FnMut(usize, usize) -> u8) -> Self {
+        let pixels = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| pixel(x, y))
+            .collect();
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn from_text(text: &str, palette: &Palette, scale: usize) -> Self {
+        // Short lines are padded with background
+        let (columns, rows) = text_size(text);
+        let mut image = Self::new(columns * scale, rows * scale);
+        for (row, line) in text.lines().take(rows).enumerate() {
+            for (column, c) in line.chars().enumerate() {
+                let index = palette.index(c);
+                for y in row * scale..(row + 1) * scale {
+                    let start = y * image.width + column * scale;
+                    image.pixels[start..start + scale].fill(index);
+                }
+            }
+        }
+        image
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> u8 {
+        // Anything outside the image is background
+        if x < self.width && y < self.height {
+            self.pixels[y * self.width + x]
+        } else {
+            0
+        }
+    }
+
+    pub fn difference(&self, other: &Self) -> Option<(usize, usize, usize, usize)> {
+        // The smallest box, as left, top, right and bottom, that holds every pixel that differs
+        assert_eq!((self.width, self.height), (other.width, other.height));
+        let rows: Vec<(&[u8], &[u8])> = self.rows().zip(other.rows()).collect();
+        let top = rows.iter().position(|(a, b)| a != b)?;
+        let bottom = rows.iter().rposition(|(a, b)| a != b)?;
+        let (mut left, mut right) = (self.width, 0);
+        for (a, b) in rows[top..=bottom].iter() {
+            if let Some(x) = a.iter().zip(b.iter()).position(|(a, b)| a != b) {
+                left = left.min(x);
+                right = right.max(a.iter().zip(b.iter()).rposition(|(a, b)| a != b).unwrap());
+            }
+        }
+        Some((left, top, right, bottom))
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[u8]> {
+        self.pixels.chunks(self.width.max(1))
+    }
+
+    pub fn to_ppm(&self, palette: &Palette) -> Vec<u8> {
+        let mut output = format!("P6\n{} {}\n255\n", self.width, self.height).into_bytes();
+        for &i in self.pixels.iter() {
+            output.extend_from_slice(&palette.colours()[i as usize]);
+        }
+        output
+    }
+
+    pub fn to_png(&self, palette: &Palette) -> Vec<u8> {
+        let mut output = b"\x89PNG\r\n\x1a\n".to_vec();
+
+        let mut header = Vec::new();
+        header.extend_from_slice(&(self.width as u32).to_be_bytes());
+        header.extend_from_slice(&(self.height as u32).to_be_bytes());
+        header.extend_from_slice(&[8, 3, 0, 0, 0]); // 8 bit palette indexes, no interlacing
+        png_chunk(&mut output, b"IHDR", &header);
+
+        let colours: Vec<u8> = palette.colours().iter().flatten().copied().collect();
+        png_chunk(&mut output, b"PLTE", &colours);
+
+        // Each row starts with its filter type, which is always none
+        let mut data = Vec::with_capacity((self.width + 1) * self.height);
+        for row in self.rows() {
+            data.push(0);
+            data.extend_from_slice(row);
+        }
+        png_chunk(&mut output, b"IDAT", &zlib_stored(&data));
+
+        png_chunk(&mut output, b"IEND", &[]);
+        output
+    }
+}
+
+fn png_chunk(output: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    output.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = output.len();
+    output.extend_from_slice(kind);
+    output.extend_from_slice(data);
+    let crc = crc32(&output[start..]);
+    output.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    // Without a compression crate the data is stored as is, in blocks of up to 65535 bytes
+    let mut output = vec![0x78, 0x01];
+    let blocks: Vec<&[u8]> = data.chunks(0xffff).collect();
+    for (i, block) in blocks.iter().enumerate() {
+        let last = i + 1 == blocks.len();
+        output.push(if last == true { 1 } else { 0 });
+        output.extend_from_slice(&(block.len() as u16).to_le_bytes());
+        output.extend_from_slice(&(!(block.len() as u16)).to_le_bytes());
+        output.extend_from_slice(block);
+    }
+    if blocks.is_empty() == true {
+        output.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+
+    let (a, b) = data.iter().fold((1u32, 0u32), |(a, b), &byte| {
+        let a = (a + byte as u32) % 65521;
+        (a, (b + a) % 65521)
+    });
+    output.extend_from_slice(&((b << 16) | a).to_be_bytes());
+    output
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_from_text() {
+        let palette = Palette::new([0, 0, 0])
+            .with('#', [255, 255, 255])
+            .with('~', [0, 0, 255])
+            .with_overrides("~=1e90ff,|=00ff00");
+        assert_eq!(palette.colours()[2], [0x1e, 0x90, 0xff]);
+        assert_eq!(palette.index('|'), 3);
+        assert_eq!(palette.index('?'), 0);
+
+        // Each cell is two pixels square and the short row is padded
+        let image = Image::from_text("#~\n|\n", &palette, 2);
+        assert_eq!((image.width(), image.height()), (4, 4));
+        let rows: Vec<&[u8]> = image.rows().collect();
+        assert_eq!(
+            rows,
+            [[1, 1, 2, 2], [1, 1, 2, 2], [3, 3, 0, 0], [3, 3, 0, 0]]
+        );
+
+        let ppm = image.to_ppm(&palette);
+        assert!(ppm.starts_with(b"P6\n4 4\n255\n"));
+        assert_eq!(ppm.len(), 11 + 4 * 4 * 3);
+    }
+
+    #[test]
+    fn test_png() {
+        assert_eq!(crc32(b"IEND"), 0xae42_6082);
+
+        let palette = Palette::new([0, 0, 0]).with('#', [255, 0, 0]);
+        let png = Image::from_text("#.\n.#", &palette, 1).to_png(&palette);
+        assert!(png.starts_with(b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR\0\0\0\x02\0\0\0\x02"));
+        assert!(png.ends_with(b"\0\0\0\0IEND\xae\x42\x60\x82"));
+
+        // The rows are stored uncompressed, each after a filter byte, then the checksum
+        let idat = png.windows(4).position(|w| w == b"IDAT").unwrap();
+        assert_eq!(
+            &png[idat + 4..idat + 21],
+            &[0x78, 0x01, 1, 6, 0, 0xf9, 0xff, 0, 1, 0, 0, 0, 1, 0, 0x0c, 0, 0x03]
+        );
+    }
+}
@@ -0,0 +1,276 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+// Finds which value belongs to each key, where every key has a different value and pieces of
+// evidence (numbered by the caller, e.g. sample index) each limit what one key's value could be.
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum MatchingError<K, V> {
+    // Evidence about a key that was never given to new()
+    UnknownKey {
+        key: K,
+        evidence: usize,
+    },
+    // This evidence ruled out every value left for the key, given the earlier evidence about it
+    Contradiction {
+        key: K,
+        evidence: usize,
+        earlier: Vec<usize>,
+    },
+    // Both keys can only have this value
+    SharedValue {
+        value: V,
+        keys: (K, K),
+    },
+    // Every value must be used, but no key can have this one
+    UnusedValue {
+        value: V,
+    },
+    // There's no way to give every key a different value
+    NoAssignment,
+    // More than one assignment fits the evidence. Lists the keys that aren't pinned down.
+    Ambiguous {
+        keys: Vec<K>,
+    },
+}
+
+pub struct Matching<K, V> {
+    candidates: BTreeMap<K, BTreeSet<V>>,
+    evidence: BTreeMap<K, Vec<usize>>,
+    values: BTreeSet<V>,
+}
+
+impl<K: Copy + Ord, V: Copy + Ord> Matching<K, V> {
+    pub fn new(keys: impl IntoIterator<Item = K>, values: impl IntoIterator<Item = V>) -> Self {
+        // With no evidence yet, any key could have any value
+        let values: BTreeSet<V> = values.into_iter().collect();
+        Self {
+            candidates: keys.into_iter().map(|k| (k, values.clone())).collect(),
+            evidence: BTreeMap::new(),
+            values,
+        }
+    }
+
+    pub fn candidates(&self, key: K) -> Option<&BTreeSet<V>> {
+        self.candidates.get(&key)
+    }
+
+    pub fn restrict(
+        &mut self,
+        key: K,
+        evidence: usize,
+        allowed: impl IntoIterator<Item = V>,
+    ) -> Result<(), MatchingError<K, V>> {
+        let candidates = self
+            .candidates
+            .get_mut(&key)
+            .ok_or(MatchingError::UnknownKey { key, evidence })?;
+        let earlier = self.evidence.entry(key).or_default();
+        let allowed: BTreeSet<V> = allowed.into_iter().collect();
+        if candidates.iter().any(|v| allowed.contains(v)) == false {
+            return Err(MatchingError::Contradiction {
+                key,
+                evidence,
+                earlier: earlier.clone(),
+            });
+        }
+
+        candidates.retain(|v| allowed.contains(v));
+        earlier.push(evidence);
+        Ok(())
+    }
+
+    pub fn propagate(&mut self) -> Result<(), MatchingError<K, V>> {
+        // Rules out values until nothing changes. Doesn't guess, so may leave several candidates.
+        let bijection = self.candidates.len() == self.values.len();
+        loop {
+            let mut changed = false;
+
+            // A key with only one value left takes it from every other key
+            let fixed: Vec<(K, V)> = self
+                .candidates
+                .iter()
+                .filter(|(_, candidates)| candidates.len() == 1)
+                .map(|(&key, candidates)| (key, *candidates.iter().next().unwrap()))
+                .collect();
+            for (key, value) in fixed {
+                for (&other, candidates) in self.candidates.iter_mut() {
+                    if other != key && candidates.remove(&value) == true {
+                        changed = true;
+                        if candidates.is_empty() == true {
+                            return Err(MatchingError::SharedValue {
+                                value,
+                                keys: (key, other),
+                            });
+                        }
+                    }
+                }
+            }
+
+            // When every value must be used, a value that only one key can have belongs to it
+            if bijection == true {
+                for &value in &self.values {
+                    let keys: Vec<K> = self
+                        .candidates
+                        .iter()
+                        .filter(|(_, candidates)| candidates.contains(&value))
+                        .map(|(&key, _)| key)
+                        .collect();
+                    match keys[..] {
+                        [] => return Err(MatchingError::UnusedValue { value }),
+                        [key] => {
+                            let candidates = self.candidates.get_mut(&key).unwrap();
+                            if candidates.len() > 1 {
+                                *candidates = BTreeSet::from([value]);
+                                changed = true;
+                            }
+                        }
+                        _ => (),
+                    }
+                }
+            }
+
+            if changed == false {
+                return Ok(());
+            }
+        }
+    }
+
+    fn search(
+        &self,
+        keys: &[K],
+        current: &mut BTreeMap<K, V>,
+        results: &mut Vec<BTreeMap<K, V>>,
+        limit: usize,
+    ) {
+        match keys.split_first() {
+            None => results.push(current.clone()),
+            Some((&key, rest)) => {
+                for &value in &self.candidates[&key] {
+                    if results.len() >= limit {
+                        return;
+                    }
+                    if current.values().any(|&v| v == value) == false {
+                        current.insert(key, value);
+                        self.search(rest, current, results, limit);
+                        current.remove(&key);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn assignments(&self) -> Vec<BTreeMap<K, V>> {
+        // Every way of giving each key a different value from its candidates. Without much
+        // evidence there can be a huge number of these, so propagate first.
+        let keys: Vec<K> = self.candidates.keys().copied().collect();
+        let mut results = Vec::new();
+        self.search(&keys, &mut BTreeMap::new(), &mut results, usize::MAX);
+        results
+    }
+
+    pub fn solve(&mut self) -> Result<BTreeMap<K, V>, MatchingError<K, V>> {
+        self.propagate()?;
+
+        // Only need to find a second assignment to know it's ambiguous
+        let keys: Vec<K> = self.candidates.keys().copied().collect();
+        let mut results = Vec::new();
+        self.search(&keys, &mut BTreeMap::new(), &mut results, 2);
+        match results.len() {
+            0 => Err(MatchingError::NoAssignment),
+            1 => Ok(results.pop().unwrap()),
+            _ => Err(MatchingError::Ambiguous {
+                keys: keys
+                    .into_iter()
+                    .filter(|k| self.candidates[k].len() > 1)
+                    .collect(),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_solve() {
+        let mut matching = Matching::new(['a', 'b', 'c'], [1, 2, 3]);
+        matching.restrict('a', 0, [1, 2]).unwrap();
+        matching.restrict('b', 1, [2]).unwrap();
+        matching.restrict('a', 2, [1, 2, 3]).unwrap();
+        assert_eq!(matching.candidates('a'), Some(&BTreeSet::from([1, 2])));
+
+        // Nothing is known about c, but it must have the value left over
+        let decoded = matching.solve().unwrap();
+        assert_eq!(decoded, BTreeMap::from([('a', 1), ('b', 2), ('c', 3)]));
+    }
+
+    #[test]
+    fn test_assignments() {
+        let mut matching = Matching::new(['a', 'b', 'c'], [1, 2, 3]);
+        matching.restrict('a', 0, [1]).unwrap();
+        matching.propagate().unwrap();
+        assert_eq!(
+            matching.assignments(),
+            vec![
+                BTreeMap::from([('a', 1), ('b', 2), ('c', 3)]),
+                BTreeMap::from([('a', 1), ('b', 3), ('c', 2)]),
+            ]
+        );
+        assert_eq!(
+            matching.solve(),
+            Err(MatchingError::Ambiguous {
+                keys: vec!['b', 'c']
+            })
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut matching = Matching::new(['a', 'b', 'c'], [1, 2, 3]);
+        matching.restrict('a', 0, [1, 2]).unwrap();
+        matching.restrict('a', 1, [2, 3]).unwrap();
+        assert_eq!(
+            matching.restrict('a', 2, [1, 3]),
+            Err(MatchingError::Contradiction {
+                key: 'a',
+                evidence: 2,
+                earlier: vec![0, 1]
+            })
+        );
+        assert_eq!(
+            matching.restrict('d', 3, [1]),
+            Err(MatchingError::UnknownKey {
+                key: 'd',
+                evidence: 3
+            })
+        );
+        assert_eq!(matching.candidates('d'), None);
+        assert!(matching.evidence.contains_key(&'d') == false);
+
+        matching.restrict('b', 4, [2]).unwrap();
+        assert_eq!(
+            matching.solve(),
+            Err(MatchingError::SharedValue {
+                value: 2,
+                keys: ('a', 'b')
+            })
+        );
+
+        let mut matching = Matching::new(['a', 'b', 'c'], [1, 2, 3]);
+        matching.restrict('a', 0, [1, 2]).unwrap();
+        matching.restrict('b', 1, [1, 2]).unwrap();
+        matching.restrict('c', 2, [1, 2]).unwrap();
+        assert_eq!(
+            matching.solve(),
+            Err(MatchingError::UnusedValue { value: 3 })
+        );
+
+        // Fewer keys than values, so not every value needs using
+        let mut matching = Matching::new(['a', 'b', 'c'], [1, 2, 3, 4]);
+        matching.restrict('a', 0, [1, 2]).unwrap();
+        matching.restrict('b', 1, [1, 2]).unwrap();
+        matching.restrict('c', 2, [1, 2]).unwrap();
+        assert_eq!(matching.solve(), Err(MatchingError::NoAssignment));
+    }
+}
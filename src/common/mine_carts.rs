@@ -0,0 +1,510 @@
+use crate::common::Cardinal;
+use crate::common::FromChar;
+use crate::common::Grid;
+use crate::common::Palette;
+use crate::common::Point;
+use crate::common::Simulation;
+use crate::common::ToChar;
+use crate::common::Turn;
+use std::fmt;
+
+// Carts running around a track, as in day 13. What a cart does at an intersection and what happens
+// when two carts meet are both chosen by the caller, so other rules and other layouts can be tried.
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum NextTurn {
+    Left,
+    Straight,
+    Right,
+}
+
+pub trait TurnPolicy: Clone {
+    // Each cart has its own copy, so a policy can remember what that cart did before
+    fn choose(&mut self, intersection: Point) -> NextTurn;
+}
+
+#[derive(Clone, Debug)]
+pub struct TurnCycle {
+    turns: Vec<NextTurn>,
+    next: usize,
+}
+
+impl TurnCycle {
+    pub fn new(turns: &[NextTurn]) -> Self {
+        assert!(turns.is_empty() == false, "Nothing to cycle through");
+        Self {
+            turns: turns.to_vec(),
+            next: 0,
+        }
+    }
+}
+
+impl Default for TurnCycle {
+    // The puzzle's rule
+    fn default() -> Self {
+        Self::new(&[NextTurn::Left, NextTurn::Straight, NextTurn::Right])
+    }
+}
+
+impl TurnPolicy for TurnCycle {
+    fn choose(&mut self, _intersection: Point) -> NextTurn {
+        let turn = self.turns[self.next];
+        self.next = (self.next + 1) % self.turns.len();
+        turn
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Collision {
+    Annihilate,  // Both carts are taken off the track
+    BounceBack,  // Both carts turn around
+    PassThrough, // Both carts carry on as if nothing happened
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Event {
+    Turn {
+        tick: u32,
+        cart: usize,
+        location: Point, // The intersection
+        turn: NextTurn,
+    },
+    Collision {
+        tick: u32,
+        location: Point,
+        carts: (usize, usize), // The one that moved, then the one it hit
+        outcome: Collision,
+    },
+}
+
+#[derive(Clone)]
+struct Cart<P> {
+    id: usize, // Carts are numbered in reading order from where they start
+    location: Point,
+    orientation: Cardinal,
+    policy: P,
+    crashed: bool,
+}
+
+#[derive(Clone, Hash)]
+enum Track {
+    Horizontal,
+    Vertical,
+    CornerA, // On a square, this is the upper left corner or the lower right corner
+    CornerB, // On a square, this is the upper right corner or the lower left corner
+    Intersection,
+}
+
+impl Track {
+    fn steer(&self, orientation: Cardinal) -> Cardinal {
+        // Which way a cart leaves, having come in facing the given way. Intersections are left to
+        // the cart's policy.
+        match self {
+            Self::Horizontal | Self::Vertical | Self::Intersection => orientation,
+            Self::CornerA => match orientation {
+                Cardinal::North | Cardinal::South => orientation.turn(Turn::Right),
+                Cardinal::East | Cardinal::West => orientation.turn(Turn::Left),
+            },
+            Self::CornerB => match orientation {
+                Cardinal::North | Cardinal::South => orientation.turn(Turn::Left),
+                Cardinal::East | Cardinal::West => orientation.turn(Turn::Right),
+            },
+        }
+    }
+}
+
+impl FromChar for Track {
+    fn from_char(c: char) -> Option<Self> {
+        match c {
+            '-' | '<' | '>' => Some(Self::Horizontal),
+            '|' | '^' | 'v' => Some(Self::Vertical),
+            '/' => Some(Self::CornerA),
+            '\\' => Some(Self::CornerB),
+            '+' => Some(Self::Intersection),
+            ' ' => None,
+            _ => panic!("Unknown input character"),
+        }
+    }
+}
+
+impl ToChar for Track {
+    fn to_char(&self) -> char {
+        match self {
+            Self::Horizontal => '-',
+            Self::Vertical => '|',
+            Self::CornerA => '/',
+            Self::CornerB => '\\',
+            Self::Intersection => '+',
+        }
+    }
+}
+
+pub fn palette() -> Palette {
+    // Carts are drawn as arrows and crashes as an X
+    let track = [160, 160, 160];
+    let cart = [230, 60, 40];
+    Palette::new([20, 20, 30])
+        .with(Track::Horizontal.to_char(), track)
+        .with(Track::Vertical.to_char(), track)
+        .with(Track::CornerA.to_char(), track)
+        .with(Track::CornerB.to_char(), track)
+        .with(Track::Intersection.to_char(), [220, 220, 220])
+        .with('^', cart)
+        .with('v', cart)
+        .with('<', cart)
+        .with('>', cart)
+        .with('X', [255, 220, 0])
+}
+
+const DEFAULT_TICKS: u32 = 10_000; // When carts can't be destroyed, so there's no natural end
+
+#[derive(Clone)]
+pub struct TrackMap<P: TurnPolicy> {
+    tracks: Grid<Track>,
+    carts: Vec<Cart<P>>,
+    collision: Collision,
+    ticks: u32,
+    events: Vec<Event>,
+    stop: fn(&Self) -> bool,
+}
+
+impl<P: TurnPolicy> TrackMap<P> {
+    pub fn new(input: &str, policy: P, collision: Collision) -> Self {
+        // Carts sit on straight track, so the input is read once for the track and again for the carts
+        let tracks = Grid::sparse_from_string(input);
+        let carts = Grid::<char>::sparse_from_string(input)
+            .iter()
+            .filter(|&(_p, &c)| c == '^' || c == 'v' || c == '>' || c == '<')
+            .enumerate()
+            .map(|(id, (p, &c))| Cart {
+                id,
+                location: p,
+                orientation: Cardinal::from_arrow(c),
+                policy: policy.clone(),
+                crashed: false,
+            })
+            .collect();
+
+        Self {
+            tracks,
+            carts,
+            collision,
+            ticks: 0,
+            events: Vec::new(),
+            stop: |track_map| match track_map.collision {
+                Collision::Annihilate => track_map.carts_left() <= 1,
+                Collision::BounceBack | Collision::PassThrough => track_map.ticks >= DEFAULT_TICKS,
+            },
+        }
+    }
+
+    pub fn stop_when(mut self, stop: fn(&Self) -> bool) -> Self {
+        // By default the carts run until at most one is left, or for a fixed number of ticks if
+        // collisions don't destroy them
+        self.stop = stop;
+        self
+    }
+
+    pub fn ticks(&self) -> u32 {
+        self.ticks
+    }
+
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+
+    pub fn collisions(&self) -> impl Iterator<Item = (u32, Point, (usize, usize))> + '_ {
+        self.events.iter().filter_map(|&event| match event {
+            Event::Collision {
+                tick,
+                location,
+                carts,
+                outcome: _,
+            } => Some((tick, location, carts)),
+            Event::Turn { .. } => None,
+        })
+    }
+
+    pub fn first_collision(&self) -> Option<Point> {
+        self.collisions()
+            .next()
+            .map(|(_tick, location, _carts)| location)
+    }
+
+    pub fn carts_left(&self) -> usize {
+        self.carts.iter().filter(|c| c.crashed == false).count()
+    }
+
+    pub fn survivor(&self) -> Option<Point> {
+        // The only cart that was never destroyed in a collision
+        let destroyed: Vec<usize> = self
+            .events
+            .iter()
+            .flat_map(|event| match *event {
+                Event::Collision {
+                    carts: (a, b),
+                    outcome: Collision::Annihilate,
+                    ..
+                } => vec![a, b],
+                _ => Vec::new(),
+            })
+            .collect();
+        let mut left = self
+            .carts
+            .iter()
+            .filter(|c| destroyed.contains(&c.id) == false);
+        match (left.next(), left.next()) {
+            (Some(cart), None) => Some(cart.location),
+            _ => None,
+        }
+    }
+
+    pub fn run(&mut self) {
+        while self.is_finished() == false {
+            self.tick();
+        }
+    }
+
+    fn move_carts(&mut self) {
+        // Carts must be processed in order. Sort them by row and then by column.
+        self.ticks += 1;
+        self.carts
+            .sort_by(|a, b| Point::cmp_y_x(&a.location, &b.location));
+
+        for i in 0..self.carts.len() {
+            if self.carts[i].crashed == true {
+                // Don't process crashed carts
+                continue;
+            }
+
+            let next_point = self.carts[i].location.step(self.carts[i].orientation, 1);
+            self.carts[i].location = next_point;
+            let track = match self.tracks.get(next_point) {
+                Some(track) => track.clone(),
+                None => panic!("No track found: {}", next_point),
+            };
+
+            let others: Vec<usize> = (0..self.carts.len())
+                .filter(|&j| j != i && self.carts[j].crashed == false)
+                .filter(|&j| self.carts[j].location == next_point)
+                .collect();
+            for &j in others.iter() {
+                self.events.push(Event::Collision {
+                    tick: self.ticks,
+                    location: next_point,
+                    carts: (self.carts[i].id, self.carts[j].id),
+                    outcome: self.collision,
+                });
+                match self.collision {
+                    Collision::Annihilate => {
+                        self.carts[i].crashed = true;
+                        self.carts[j].crashed = true;
+                    }
+                    Collision::BounceBack => {
+                        // The cart that was hit has already been steered by this track, so it's
+                        // steered again as if it had come in the other way
+                        let reverse = self.carts[j].orientation.opposite();
+                        self.carts[j].orientation = track.steer(reverse);
+                    }
+                    Collision::PassThrough => (),
+                }
+            }
+            if self.carts[i].crashed == true {
+                continue;
+            }
+            if others.is_empty() == false && self.collision == Collision::BounceBack {
+                // Back the way it came
+                self.carts[i].orientation = self.carts[i].orientation.opposite();
+                continue;
+            }
+
+            let cart = &mut self.carts[i];
+            cart.orientation = match track {
+                Track::Intersection => {
+                    let turn = cart.policy.choose(next_point);
+                    self.events.push(Event::Turn {
+                        tick: self.ticks,
+                        cart: cart.id,
+                        location: next_point,
+                        turn,
+                    });
+                    match turn {
+                        NextTurn::Left => cart.orientation.turn(Turn::Left),
+                        NextTurn::Straight => cart.orientation,
+                        NextTurn::Right => cart.orientation.turn(Turn::Right),
+                    }
+                }
+                _ => track.steer(cart.orientation),
+            };
+        }
+    }
+}
+
+impl<P: TurnPolicy> Simulation for TrackMap<P> {
+    fn tick(&mut self) {
+        self.move_carts();
+    }
+
+    fn is_finished(&self) -> bool {
+        (self.stop)(self)
+    }
+}
+
+impl<P: TurnPolicy> fmt::Display for TrackMap<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let output = self.tracks.render(|p, track| {
+            let mut here = self.carts.iter().filter(|c| c.location == p);
+            if let Some(cart) = here.clone().find(|c| c.crashed == false) {
+                cart.orientation.to_arrow()
+            } else if here.next().is_some() {
+                'X'
+            } else {
+                track.map_or(' ', |t| t.to_char())
+            }
+        });
+        writeln!(f, "{}", output)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const EXAMPLE: &str = r"/->-\
+|   |  /----\
+| /-+--+-\  |
+| | |  | v  |
+\-+-/  \-+--/
+    \------/";
+
+    const FACING: &str = r"/>--<\
+\----/";
+
+    #[test]
+    fn test_events() {
+        let mut track_map = TrackMap::new(EXAMPLE, TurnCycle::default(), Collision::Annihilate);
+        track_map.run();
+        assert_eq!(track_map.ticks(), 14);
+        assert_eq!(track_map.carts_left(), 0);
+        assert_eq!(track_map.survivor(), None);
+
+        // Cart 0 runs into cart 1, having been through three intersections on the way
+        assert_eq!(
+            track_map.collisions().collect::<Vec<_>>(),
+            [(14, Point { x: 7, y: 3 }, (0, 1))]
+        );
+        let turns: Vec<&Event> = track_map
+            .events()
+            .iter()
+            .filter(|e| matches!(e, Event::Turn { cart: 0, .. }))
+            .collect();
+        assert_eq!(turns.len(), 3);
+        assert_eq!(
+            turns[0],
+            &Event::Turn {
+                tick: 4,
+                cart: 0,
+                location: Point { x: 4, y: 2 },
+                turn: NextTurn::Left,
+            }
+        );
+    }
+
+    #[test]
+    fn test_turn_policy() {
+        // Two loops that cross. Going straight on, each cart keeps to its own loop and they meet
+        // later than with the puzzle's rule.
+        let input = r"/>--\
+|   |
+| /-+-\
+| | | |
+\-+-/ v
+  \---/";
+        let run = |policy| {
+            let mut track_map = TrackMap::new(input, policy, Collision::Annihilate);
+            track_map.run();
+            track_map
+        };
+
+        let puzzle = run(TurnCycle::default());
+        assert_eq!(
+            puzzle.collisions().collect::<Vec<_>>(),
+            [(19, Point { x: 4, y: 2 }, (0, 1))]
+        );
+
+        let straight = run(TurnCycle::new(&[NextTurn::Straight]));
+        assert_eq!(
+            straight.collisions().collect::<Vec<_>>(),
+            [(38, Point { x: 4, y: 2 }, (1, 0))]
+        );
+        let cart_0: Vec<Point> = straight
+            .events()
+            .iter()
+            .filter_map(|e| match *e {
+                Event::Turn {
+                    cart: 0,
+                    location,
+                    turn: NextTurn::Straight,
+                    ..
+                } => Some(location),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            cart_0,
+            [
+                Point { x: 4, y: 2 },
+                Point { x: 2, y: 4 },
+                Point { x: 4, y: 2 },
+                Point { x: 2, y: 4 },
+                Point { x: 4, y: 2 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_collisions() {
+        let stop = |track_map: &TrackMap<TurnCycle>| track_map.ticks() == 3;
+        let run = |collision| {
+            let mut track_map =
+                TrackMap::new(FACING, TurnCycle::default(), collision).stop_when(stop);
+            track_map.run();
+            track_map
+        };
+
+        // The carts meet in the second tick
+        let annihilated = run(Collision::Annihilate);
+        assert_eq!(annihilated.carts_left(), 0);
+        assert_eq!(annihilated.first_collision(), Some(Point { x: 3, y: 0 }));
+        assert_eq!(annihilated.to_string(), "/--X-\\\n\\----/\n\n");
+
+        // The cart that was hit hasn't moved yet that tick, so it gets away first
+        let bounced = run(Collision::BounceBack);
+        assert_eq!(bounced.carts_left(), 2);
+        assert_eq!(bounced.collisions().count(), 1);
+        assert_eq!(bounced.to_string(), "/-<--v\n\\----/\n\n");
+
+        // Without a stop of their own, carts that can't be destroyed still come to an end
+        let mut bouncing = TrackMap::new(FACING, TurnCycle::default(), Collision::BounceBack);
+        bouncing.run();
+        assert_eq!(bouncing.ticks(), DEFAULT_TICKS);
+        assert_eq!(bouncing.carts_left(), 2);
+
+        let passed = run(Collision::PassThrough);
+        assert_eq!(passed.collisions().count(), 1);
+        assert_eq!(passed.to_string(), "/<-->\\\n\\----/\n\n");
+    }
+
+    #[test]
+    fn test_bounce_off_corner() {
+        // The cart that's hit has already turned the corner, so it goes back round it
+        let input = r"/<\
+^ |
+\-/";
+        let mut track_map = TrackMap::new(input, TurnCycle::default(), Collision::BounceBack);
+        track_map.tick();
+        track_map.tick();
+        assert_eq!(track_map.collisions().count(), 1);
+        assert_eq!(track_map.to_string(), "/>\\\nv |\n\\-/\n\n");
+    }
+}
@@ -1,9 +1,34 @@
+mod automaton;
+mod bit_automaton;
 mod cardinal;
+pub mod combat;
+mod cycle;
+pub mod elfcode;
+mod gif;
+mod grid;
+mod image;
 mod linked_list_circ;
+mod matching;
+pub mod mine_carts;
 mod modulus;
+mod ocr;
+mod playback;
 mod point;
+mod recording;
+mod search;
 
+pub use automaton::*;
+pub use bit_automaton::*;
 pub use cardinal::*;
+pub use cycle::*;
+pub use gif::*;
+pub use grid::*;
+pub use image::*;
 pub use linked_list_circ::*;
+pub use matching::*;
 pub use modulus::*;
+pub use ocr::*;
+pub use playback::*;
 pub use point::*;
+pub use recording::*;
+pub use search::*;
@@ -0,0 +1,107 @@
+use crate::common::Point;
+use std::collections::HashSet;
+
+// Reads messages written in the block letters Advent of Code uses for pictures. Each letter is six
+// cells wide and ten tall, with empty columns between letters.
+
+const HEIGHT: usize = 10;
+const WIDTH: usize = 6;
+const LETTERS: &str = "ABCEFGHJKLNPRXZ";
+const FONT: &str = "
+..##....#####....####...######..######...####...#....#.....###..#....#..#.......#....#..#####...#####...#....#..######
+.#..#...#....#..#....#..#.......#.......#....#..#....#......#...#...#...#.......##...#..#....#..#....#..#....#.......#
+#....#..#....#..#.......#.......#.......#.......#....#......#...#..#....#.......##...#..#....#..#....#...#..#........#
+#....#..#....#..#.......#.......#.......#.......#....#......#...#.#.....#.......#.#..#..#....#..#....#...#..#.......#.
+#....#..#####...#.......#####...#####...#.......######......#...##......#.......#.#..#..#####...#####.....##.......#..
+######..#....#..#.......#.......#.......#..###..#....#......#...##......#.......#..#.#..#.......#..#......##......#...
+#....#..#....#..#.......#.......#.......#....#..#....#......#...#.#.....#.......#..#.#..#.......#...#....#..#....#....
+#....#..#....#..#.......#.......#.......#....#..#....#..#...#...#..#....#.......#...##..#.......#...#....#..#...#.....
+#....#..#....#..#....#..#.......#.......#...##..#....#..#...#...#...#...#.......#...##..#.......#....#..#....#..#.....
+#....#..#####....####...######..#........###.#..#....#...###....#....#..######..#....#..#.......#....#..#....#..######";
+
+fn read_letter(rows: &[String]) -> Option<char> {
+    let font: Vec<&str> = FONT.trim().lines().collect();
+    LETTERS
+        .chars()
+        .enumerate()
+        .find(|&(i, _c)| {
+            let start = i * (WIDTH + 2);
+            rows.iter()
+                .zip(font.iter())
+                .all(|(row, line)| row == &line[start..start + WIDTH])
+        })
+        .map(|(_i, c)| c)
+}
+
+pub fn read_message(points: &[Point]) -> Option<String> {
+    // Gives up if the points aren't all in one line of letters it knows
+    let min_x = points.iter().map(|p| p.x).min()?;
+    let max_x = points.iter().map(|p| p.x).max()?;
+    let min_y = points.iter().map(|p| p.y).min()?;
+    let max_y = points.iter().map(|p| p.y).max()?;
+    if (max_y - min_y + 1) as usize != HEIGHT {
+        return None;
+    }
+
+    // Each run of columns with something in them is a letter
+    let lit: HashSet<Point> = points.iter().copied().collect();
+    let column = |x: i32| -> String {
+        (min_y..=max_y)
+            .map(|y| {
+                if lit.contains(&Point { x, y }) == true {
+                    '#'
+                } else {
+                    '.'
+                }
+            })
+            .collect()
+    };
+    let mut message = String::new();
+    let mut letter: Vec<String> = Vec::new();
+    for x in min_x..=max_x + 1 {
+        let c = column(x);
+        if c.contains('#') == true {
+            letter.push(c);
+        } else if letter.is_empty() == false {
+            // Turn the columns into rows
+            let rows: Vec<String> = (0..HEIGHT)
+                .map(|y| letter.iter().map(|c| c.as_bytes()[y] as char).collect())
+                .collect();
+            message.push(read_letter(&rows)?);
+            letter.clear();
+        }
+    }
+    Some(message)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn to_points(text: &str) -> Vec<Point> {
+        text.trim()
+            .lines()
+            .enumerate()
+            .flat_map(|(y, line)| {
+                line.chars()
+                    .enumerate()
+                    .filter(|&(_x, c)| c == '#')
+                    .map(move |(x, _c)| Point {
+                        x: x as i32 - 50,
+                        y: y as i32 + 7,
+                    })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_read_message() {
+        assert_eq!(read_message(&to_points(FONT)), Some(LETTERS.to_string()));
+
+        // Wrong height, or a letter it doesn't know
+        assert_eq!(read_message(&to_points("#\n#")), None);
+        let unknown = "######\n".repeat(HEIGHT);
+        assert_eq!(read_message(&to_points(&unknown)), None);
+        assert_eq!(read_message(&[]), None);
+    }
+}
@@ -0,0 +1,183 @@
+use crate::common::Simulation;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Watches a simulation run in the terminal, redrawing each frame with the cells that changed
+// highlighted. Set AOC_PLAYBACK to the starting frames per second (or leave it empty for the
+// default) and the days that support it will play before solving. Keys while playing:
+//   space  pause / resume
+//   s      step one frame while paused
+//   + -    double / halve the speed
+//   q      stop watching
+
+const ENV_VAR: &str = "AOC_PLAYBACK";
+const DEFAULT_FPS: f64 = 10.0;
+const MAX_FRAMES: usize = 1000; // For simulations that never finish
+
+const CLEAR: &str = "\x1b[2J\x1b[H";
+const HIGHLIGHT: &str = "\x1b[7m";
+const RESET: &str = "\x1b[0m";
+
+enum Action {
+    Advance,
+    Redraw,
+    Quit,
+}
+
+pub fn highlight_changes(previous: &str, current: &str) -> String {
+    // Each character that differs from the one in the same place in the previous frame is shown in
+    // reverse video
+    let mut output = String::new();
+    let mut previous_lines = previous.lines();
+    for line in current.lines() {
+        let mut previous_chars = previous_lines.next().unwrap_or("").chars();
+        let mut highlighting = false;
+        for c in line.chars() {
+            let changed = previous_chars.next() != Some(c);
+            if changed != highlighting {
+                output.push_str(if changed == true { HIGHLIGHT } else { RESET });
+                highlighting = changed;
+            }
+            output.push(c);
+        }
+        if highlighting == true {
+            output.push_str(RESET);
+        }
+        output.push('\n');
+    }
+    output
+}
+
+fn set_cbreak(enabled: bool) {
+    // Lets keys through without waiting for enter. If there's no terminal, keys still work once
+    // enter is pressed.
+    let args: &[&str] = if enabled == true {
+        &["-icanon", "-echo"]
+    } else {
+        &["icanon", "echo"]
+    };
+    let _ = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::null())
+        .status();
+}
+
+fn spawn_key_reader() -> Receiver<u8> {
+    // The thread is left blocked on stdin when playback ends, which is harmless as nothing else reads it
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for byte in io::stdin().lock().bytes() {
+            if byte.map(|b| sender.send(b)).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+pub fn play<S: Simulation + fmt::Display>(simulation: &mut S, fps: f64) {
+    set_cbreak(true);
+    let keys = spawn_key_reader();
+    let mut fps = fps;
+    let mut paused = false;
+    let mut frame = 0;
+    let mut previous = String::new();
+    loop {
+        let current = simulation.to_string();
+        let out_of_frames = frame + 1 >= MAX_FRAMES;
+        let finished = simulation.is_finished() || out_of_frames;
+        let status = match (finished, paused) {
+            (true, _) if out_of_frames == true => "stopped at the frame limit",
+            (true, _) => "finished",
+            (false, true) => "paused",
+            (false, false) => "playing",
+        };
+        println!(
+            "{}{}frame {}  {:.1} fps  {}  [space] pause  [s] step  [+/-] speed  [q] quit",
+            CLEAR,
+            highlight_changes(&previous, &current),
+            frame,
+            fps,
+            status
+        );
+        let _ = io::stdout().flush();
+        if finished == true {
+            break;
+        }
+
+        // Wait for the next frame, handling keys as they come in
+        let deadline = Instant::now() + Duration::from_secs_f64(1.0 / fps);
+        let action = loop {
+            let key = if paused == true {
+                keys.recv().map_err(|_| RecvTimeoutError::Disconnected)
+            } else {
+                keys.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+            };
+            match key {
+                Ok(b' ') => {
+                    paused = paused == false;
+                    break Action::Redraw;
+                }
+                Ok(b's') if paused == true => break Action::Advance,
+                Ok(b'+') => {
+                    fps *= 2.0;
+                    break Action::Redraw;
+                }
+                Ok(b'-') => {
+                    fps /= 2.0;
+                    break Action::Redraw;
+                }
+                Ok(b'q') => break Action::Quit,
+                Ok(_) => (),
+                Err(RecvTimeoutError::Timeout) => break Action::Advance,
+                Err(RecvTimeoutError::Disconnected) => {
+                    // No more keys, so just play at this speed
+                    paused = false;
+                    thread::sleep(deadline.saturating_duration_since(Instant::now()));
+                    break Action::Advance;
+                }
+            }
+        };
+        match action {
+            Action::Advance => {
+                previous = current;
+                simulation.tick();
+                frame += 1;
+            }
+            Action::Redraw => (),
+            Action::Quit => break,
+        }
+    }
+    set_cbreak(false);
+}
+
+pub fn play_if_requested<S: Simulation + Clone + fmt::Display>(simulation: &S) {
+    // Plays a copy, so the caller can go on to solve from the start
+    if let Ok(value) = std::env::var(ENV_VAR) {
+        let fps = value.parse().unwrap_or(DEFAULT_FPS);
+        play(&mut simulation.clone(), fps);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_highlight_changes() {
+        let previous = "#..\n.#.";
+        let current = "##.\n.#..\n#";
+        assert_eq!(
+            highlight_changes(previous, current),
+            "#\x1b[7m#\x1b[0m.\n.#.\x1b[7m.\x1b[0m\n\x1b[7m#\x1b[0m\n"
+        );
+
+        // Nothing to compare the first frame against, so all of it is new
+        assert_eq!(highlight_changes("", "#."), "\x1b[7m#.\x1b[0m\n");
+    }
+}
@@ -0,0 +1,142 @@
+use crate::common::text_size;
+use crate::common::Gif;
+use crate::common::Image;
+use crate::common::Palette;
+use crate::common::Simulation;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// Saves a day as pictures, using the text it draws itself with. Set AOC_RECORD to a file name and
+// the days that support it will save before solving. A .gif gets every frame in one animation, and
+// a .png or .ppm gets a numbered file per frame, e.g. water_0000.png. Colours can be changed with
+// AOC_PALETTE, e.g. AOC_PALETTE="~=1e90ff,#=8b4513".
+
+const RECORD_VAR: &str = "AOC_RECORD";
+const PALETTE_VAR: &str = "AOC_PALETTE";
+const MAX_FRAMES: usize = 1000; // For simulations that never finish
+const FRAME_DELAY: u16 = 10; // Hundredths of a second
+const TARGET_SIZE: usize = 600; // Small maps are scaled up to around this many pixels across
+
+fn scale_for((columns, rows): (usize, usize)) -> usize {
+    (TARGET_SIZE / columns.max(rows).max(1)).max(1)
+}
+
+fn encode(image: &Image, palette: &Palette, path: &Path) -> io::Result<Vec<u8>> {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("png") => Ok(image.to_png(palette)),
+        Some("ppm") => Ok(image.to_ppm(palette)),
+        Some("gif") => {
+            let mut gif = Gif::new(palette);
+            gif.add_frame(image, 0);
+            Ok(gif.finish())
+        }
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Can only save .gif, .png or .ppm",
+        )),
+    }
+}
+
+fn numbered(path: &Path, frame: usize) -> PathBuf {
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("frame");
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    path.with_file_name(format!("{}_{:04}.{}", stem, frame, extension))
+}
+
+fn for_each_frame<S: Simulation + Clone + fmt::Display>(
+    simulation: &S,
+    max_frames: usize,
+    mut f: impl FnMut(usize, &str) -> io::Result<()>,
+) -> io::Result<usize> {
+    // Runs a copy, so the caller's simulation is left at the start
+    let mut simulation = simulation.clone();
+    for frame in 0..max_frames {
+        f(frame, &simulation.to_string())?;
+        if simulation.is_finished() == true {
+            return Ok(frame + 1);
+        }
+        simulation.tick();
+    }
+    Ok(max_frames)
+}
+
+pub fn save(text: &str, palette: &Palette, path: &Path) -> io::Result<()> {
+    let image = Image::from_text(text, palette, scale_for(text_size(text)));
+    fs::write(path, encode(&image, palette, path)?)
+}
+
+pub fn record<S: Simulation + Clone + fmt::Display>(
+    simulation: &S,
+    palette: &Palette,
+    path: &Path,
+    max_frames: usize,
+) -> io::Result<usize> {
+    // Gives back how many frames were saved. The scale is set by the first frame.
+    let mut scale = None;
+    let mut image = |text: &str| {
+        let scale = *scale.get_or_insert_with(|| scale_for(text_size(text)));
+        Image::from_text(text, palette, scale)
+    };
+    if path.extension().and_then(|e| e.to_str()) != Some("gif") {
+        return for_each_frame(simulation, max_frames, |frame, text| {
+            fs::write(numbered(path, frame), encode(&image(text), palette, path)?)
+        });
+    }
+
+    let mut gif = Gif::new(palette);
+    for_each_frame(simulation, max_frames, |_frame, text| {
+        gif.add_frame(&image(text), FRAME_DELAY);
+        Ok(())
+    })?;
+    let frames = gif.frames();
+    fs::write(path, gif.finish())?;
+    Ok(frames)
+}
+
+fn requested(palette: &Palette) -> Option<(PathBuf, Palette)> {
+    let path = std::env::var(RECORD_VAR).ok()?;
+    let overrides = std::env::var(PALETTE_VAR).unwrap_or_default();
+    Some((
+        PathBuf::from(path),
+        palette.clone().with_overrides(&overrides),
+    ))
+}
+
+pub fn record_if_requested<S: Simulation + Clone + fmt::Display>(
+    simulation: &S,
+    palette: &Palette,
+) {
+    if let Some((path, palette)) = requested(palette) {
+        match record(simulation, &palette, &path, MAX_FRAMES) {
+            Ok(frames) => println!("Recorded {} frames to {}", frames, path.display()),
+            Err(e) => panic!("Couldn't record to {}: {}", path.display(), e),
+        }
+    }
+}
+
+pub fn save_if_requested(picture: &impl fmt::Display, palette: &Palette) {
+    // For days that don't change over time
+    if let Some((path, palette)) = requested(palette) {
+        match save(&picture.to_string(), &palette, &path) {
+            Ok(()) => println!("Saved {}", path.display()),
+            Err(e) => panic!("Couldn't save {}: {}", path.display(), e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_numbered() {
+        assert_eq!(
+            numbered(Path::new("out/water.png"), 12),
+            PathBuf::from("out/water_0012.png")
+        );
+        assert_eq!(scale_for((32, 10)), 18);
+        assert_eq!(scale_for((300, 1900)), 1);
+    }
+}
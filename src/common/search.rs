@@ -0,0 +1,262 @@
+use num::Zero;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
+use std::hash::Hash;
+
+// Searches over any state that can be ordered, e.g. Point, or a Point plus whatever else matters. Ties
+// are broken using that ordering (reading order for Point): goals found at the same cost are sorted,
+// and each state remembers the smallest state it was reached from at its best cost.
+
+pub struct Search<S, C> {
+    start: S,
+    costs: HashMap<S, C>,
+    previous: HashMap<S, S>,
+    goals: Vec<S>,
+}
+
+impl<S: Copy + Eq + Hash + Ord, C: Copy> Search<S, C> {
+    fn new(start: S, zero: C) -> Self {
+        Self {
+            start,
+            costs: HashMap::from([(start, zero)]),
+            previous: HashMap::new(),
+            goals: Vec::new(),
+        }
+    }
+
+    fn reach(&mut self, state: S, from: S) {
+        // Only called when from gives state its best cost, or one equal to it
+        let previous = self.previous.entry(state).or_insert(from);
+        if from < *previous {
+            *previous = from;
+        }
+    }
+
+    pub fn cost(&self, state: S) -> Option<C> {
+        self.costs.get(&state).copied()
+    }
+
+    pub fn costs(&self) -> &HashMap<S, C> {
+        // Every state reached. If the search stopped at a goal, states further away may be missing.
+        &self.costs
+    }
+
+    pub fn goals(&self) -> &[S] {
+        // All the goals at the lowest cost, in order
+        &self.goals
+    }
+
+    pub fn goal(&self) -> Option<S> {
+        self.goals.first().copied()
+    }
+
+    pub fn path(&self, to: S) -> Option<Vec<S>> {
+        // From the start to the given state, both included
+        self.costs.get(&to)?;
+        let mut path = vec![to];
+        let mut current = to;
+        while current != self.start {
+            current = self.previous[&current];
+            path.push(current);
+        }
+        path.reverse();
+        Some(path)
+    }
+}
+
+pub fn bfs<S, I>(
+    start: S,
+    mut neighbours: impl FnMut(&S) -> I,
+    mut is_goal: impl FnMut(&S) -> bool,
+) -> Search<S, usize>
+where
+    S: Copy + Eq + Hash + Ord,
+    I: IntoIterator<Item = S>,
+{
+    // Every step costs one. Stops once the closest goals are found, or when everything reachable has
+    // been visited if there are none.
+    let mut search = Search::new(start, 0);
+    let mut frontier = VecDeque::from([start]);
+    let mut goal_distance = None;
+    while let Some(state) = frontier.pop_front() {
+        let distance = search.costs[&state];
+        if goal_distance.is_some_and(|d| distance > d) {
+            break;
+        }
+        if is_goal(&state) == true {
+            goal_distance = Some(distance);
+            search.goals.push(state);
+            continue;
+        }
+
+        for next in neighbours(&state) {
+            match search.costs.get(&next) {
+                None => {
+                    search.costs.insert(next, distance + 1);
+                    search.reach(next, state);
+                    frontier.push_back(next);
+                }
+                Some(&d) if d == distance + 1 => search.reach(next, state),
+                _ => (),
+            }
+        }
+    }
+
+    search.goals.sort();
+    search
+}
+
+pub fn dijkstra<S, C, I>(
+    start: S,
+    neighbours: impl FnMut(&S) -> I,
+    cost: impl FnMut(&S, &S) -> C,
+    is_goal: impl FnMut(&S) -> bool,
+) -> Search<S, C>
+where
+    S: Copy + Eq + Hash + Ord,
+    C: Copy + Ord + Zero,
+    I: IntoIterator<Item = S>,
+{
+    astar(start, neighbours, cost, |_| C::zero(), is_goal)
+}
+
+pub fn astar<S, C, I>(
+    start: S,
+    mut neighbours: impl FnMut(&S) -> I,
+    mut cost: impl FnMut(&S, &S) -> C,
+    mut heuristic: impl FnMut(&S) -> C,
+    mut is_goal: impl FnMut(&S) -> bool,
+) -> Search<S, C>
+where
+    S: Copy + Eq + Hash + Ord,
+    C: Copy + Ord + Zero,
+    I: IntoIterator<Item = S>,
+{
+    // The heuristic must never overestimate the cost to the closest goal, and never drop by more
+    // than the cost of a step. Dijkstra is this with no heuristic.
+    let mut search = Search::new(start, C::zero());
+    let mut done: HashSet<S> = HashSet::new();
+    let mut queue = BinaryHeap::from([Reverse((heuristic(&start), C::zero(), start))]);
+    let mut goal_cost = None;
+    while let Some(Reverse((_, current_cost, state))) = queue.pop() {
+        if goal_cost.is_some_and(|c| current_cost > c) {
+            break;
+        }
+        if done.insert(state) == false {
+            // Already reached more cheaply, this entry is stale
+            continue;
+        }
+        if is_goal(&state) == true {
+            goal_cost = Some(current_cost);
+            search.goals.push(state);
+            continue;
+        }
+
+        for next in neighbours(&state) {
+            if done.contains(&next) == true {
+                continue;
+            }
+            let next_cost = current_cost + cost(&state, &next);
+            match search.costs.get(&next) {
+                Some(&c) if c < next_cost => continue,
+                Some(&c) if c == next_cost => search.reach(next, state),
+                _ => {
+                    search.costs.insert(next, next_cost);
+                    search.previous.insert(next, state);
+                    queue.push(Reverse((next_cost + heuristic(&next), next_cost, next)));
+                }
+            }
+        }
+    }
+
+    // Costs of states that were queued but never reached cheaper than that aren't certain
+    search.costs.retain(|state, _| done.contains(state));
+    search.goals.sort();
+    search
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::common::Grid;
+    use crate::common::Point;
+
+    fn open_neighbours(grid: &Grid<char>, p: Point) -> Vec<Point> {
+        grid.orthogonals(p)
+            .filter(|&(_n, &c)| c != '#')
+            .map(|(n, _c)| n)
+            .collect()
+    }
+
+    #[test]
+    fn test_bfs() {
+        let input = "
+#######
+#E..G.#
+#...#.#
+#.G.#G#
+#######";
+        let grid: Grid<char> = Grid::from_string(input);
+        let start = Point { x: 1, y: 1 };
+        let search = bfs(
+            start,
+            |&p| open_neighbours(&grid, p),
+            |&p| {
+                p == Point { x: 3, y: 1 } || p == Point { x: 2, y: 2 } || p == Point { x: 1, y: 3 }
+            },
+        );
+
+        // All three are two steps away, the first in reading order comes first
+        assert_eq!(
+            search.goals(),
+            &[
+                Point { x: 3, y: 1 },
+                Point { x: 2, y: 2 },
+                Point { x: 1, y: 3 }
+            ]
+        );
+        assert_eq!(search.cost(Point { x: 2, y: 2 }), Some(2));
+
+        // Both ways to (2, 2) are as short, the path goes through the one first in reading order
+        assert_eq!(
+            search.path(Point { x: 2, y: 2 }),
+            Some(vec![start, Point { x: 2, y: 1 }, Point { x: 2, y: 2 }])
+        );
+
+        // With no goals every reachable point is visited
+        let search = bfs(start, |&p| open_neighbours(&grid, p), |_| false);
+        assert_eq!(search.goal(), None);
+        assert_eq!(search.costs().len(), 13);
+        assert_eq!(search.cost(Point { x: 5, y: 3 }), Some(6));
+    }
+
+    #[test]
+    fn test_dijkstra() {
+        // Going through a 9 costs 9, going round costs 1 a step so is cheaper
+        let input = "
+1111
+9991
+1111";
+        let grid: Grid<char> = Grid::from_string(input);
+        let start = Point { x: 0, y: 0 };
+        let end = Point { x: 0, y: 2 };
+        let weight = |_from: &Point, to: &Point| grid.get(*to).unwrap().to_digit(10).unwrap();
+
+        let search = dijkstra(start, |&p| open_neighbours(&grid, p), weight, |&p| p == end);
+        assert_eq!(search.goal(), Some(end));
+        assert_eq!(search.cost(end), Some(8));
+        assert_eq!(search.path(end).unwrap().len(), 9);
+
+        // The same answer with a heuristic
+        let astar_search = astar(
+            start,
+            |&p| open_neighbours(&grid, p),
+            weight,
+            |&p| Point::manhattan(p, end),
+            |&p| p == end,
+        );
+        assert_eq!(astar_search.cost(end), Some(8));
+        assert_eq!(astar_search.path(end), search.path(end));
+        assert_eq!(search.path(Point { x: 3, y: 3 }), None);
+    }
+}
@@ -141,12 +141,14 @@
     What message will eventually appear in the sky?
 */
 
+use crate::common::read_message;
 use crate::common::Point;
 use nom::{
     bytes::complete::tag,
     sequence::{delimited, pair},
     IResult,
 };
+use std::convert::TryFrom;
 use std::fmt;
 
 struct StarMap {
@@ -203,38 +205,64 @@ impl StarMap {
     }
 
     fn step(&mut self, count: i32) {
-        if count < 0 {
-            for _ in count..0 {
-                for (star, vel) in self.stars.iter_mut().zip(self.velocity.iter()) {
-                    *star -= *vel;
-                }
-            }
-        } else {
-            for _ in 0..count {
-                for (star, vel) in self.stars.iter_mut().zip(self.velocity.iter()) {
-                    *star += *vel;
-                }
-            }
+        for (star, vel) in self.stars.iter_mut().zip(self.velocity.iter()) {
+            *star += (vel.x * count, vel.y * count);
         }
     }
 
-    fn step_until_minimum_range(&mut self) {
-        let calc_range_value = |(x, y): ((i32, i32), (i32, i32))| x.1 - x.0 + y.1 - y.0;
-        let mut last_range = self.get_range();
+    fn range_value_at(&self, time: i64) -> i64 {
+        // Width plus height of the box around the stars at the given time, without moving them
+        let (min_x, max_x, min_y, max_y) = self.stars.iter().zip(self.velocity.iter()).fold(
+            (i64::MAX, i64::MIN, i64::MAX, i64::MIN),
+            |(min_x, max_x, min_y, max_y), (star, vel)| {
+                let x = star.x as i64 + vel.x as i64 * time;
+                let y = star.y as i64 + vel.y as i64 * time;
+                (min_x.min(x), max_x.max(x), min_y.min(y), max_y.max(y))
+            },
+        );
+        max_x - min_x + max_y - min_y
+    }
 
-        loop {
-            self.step(1);
+    fn minimum_range_time(&self) -> Option<u32> {
+        // Each side of the box moves in a straight line until another star overtakes it, so the box
+        // can only shrink more and more slowly and then grow. The smallest is where it first stops
+        // shrinking, which a binary search finds without stepping through every second.
+        if self.stars.is_empty() == true {
+            return None;
+        }
+        let change = |time: i64| self.range_value_at(time + 1) - self.range_value_at(time);
 
-            let range = self.get_range();
-            let value = calc_range_value(range);
-            let last_value = calc_range_value(last_range);
-            if value > last_value {
-                self.step(-1); // Assume we found the minimum last time; back up a step.
-                break;
+        // Find a time after the smallest, then search back for it
+        let mut high = 1;
+        while change(high) < 0 {
+            high *= 2;
+        }
+        let mut low = 0;
+        while low < high {
+            let middle = (low + high) / 2;
+            if change(middle) < 0 {
+                low = middle + 1;
             } else {
-                last_range = range;
+                high = middle;
             }
         }
+
+        // If it stays the same size for a while there's no one time to pick
+        if change(low) == 0 {
+            return None;
+        }
+        u32::try_from(low).ok()
+    }
+
+    fn step_until_minimum_range(&mut self) -> Option<u32> {
+        // Jumps straight to the smallest box and gives back how many seconds that took
+        let time = self.minimum_range_time()?;
+        self.step(time as i32);
+        Some(time)
+    }
+
+    fn message(&self) -> Option<String> {
+        read_message(&self.stars)
     }
 }
 
@@ -260,23 +288,13 @@ impl fmt::Display for StarMap {
 #[aoc(day10, part1)]
 pub fn solve(input: &str) -> String {
     let mut star_map = StarMap::from_string(input);
-    star_map.step_until_minimum_range();
-    let message = star_map.to_string();
-    assert_eq!(
-        message.trim(),
-        "
-#....#..#.......######....##....#....#..#####....####...######
-#....#..#............#...#..#...#...#...#....#..#....#.......#
-.#..#...#............#..#....#..#..#....#....#..#............#
-.#..#...#...........#...#....#..#.#.....#....#..#...........#.
-..##....#..........#....#....#..##......#####...#..........#..
-..##....#.........#.....######..##......#....#..#..###....#...
-.#..#...#........#......#....#..#.#.....#....#..#....#...#....
-.#..#...#.......#.......#....#..#..#....#....#..#....#..#.....
-#....#..#.......#.......#....#..#...#...#....#..#...##..#.....
-#....#..######..######..#....#..#....#..#####....###.#..######"
-            .trim()
-    );
+    star_map
+        .step_until_minimum_range()
+        .expect("The stars don't settle on one smallest size");
+    //println!("{}", star_map);
+    let message = star_map.message().expect("Couldn't read the message");
+    println!("Message: {}", message);
+    assert_eq!(message, "XLZAKBGZ");
     message
 }
 
@@ -11,6 +11,7 @@ use nom::{
     sequence::{delimited, pair},
     IResult,
 };
+use std::convert::TryFrom;
 use std::fmt;
 
 struct StarMap {
@@ -67,42 +68,60 @@ impl StarMap {
     }
 
     fn step(&mut self, count: i32) {
-        if count < 0 {
-            for _ in count..0 {
-                for (star, vel) in self.stars.iter_mut().zip(self.velocity.iter()) {
-                    *star -= *vel;
-                }
-            }
-        } else {
-            for _ in 0..count {
-                for (star, vel) in self.stars.iter_mut().zip(self.velocity.iter()) {
-                    *star += *vel;
-                }
-            }
+        for (star, vel) in self.stars.iter_mut().zip(self.velocity.iter()) {
+            *star += (vel.x * count, vel.y * count);
         }
     }
 
-    fn step_until_minimum_range(&mut self) -> u32 {
-        let calc_range_value = |(x, y): ((i32, i32), (i32, i32))| x.1 - x.0 + y.1 - y.0;
-        let mut last_range = self.get_range();
-
-        let mut count = 0;
-        loop {
-            self.step(1);
-            count += 1;
-
-            let range = self.get_range();
-            let value = calc_range_value(range);
-            let last_value = calc_range_value(last_range);
-            if value > last_value {
-                self.step(-1); // Assume we found the minimum last time; back up a step.
-                count -= 1;
-                break;
+    fn range_value_at(&self, time: i64) -> i64 {
+        // Width plus height of the box around the stars at the given time, without moving them
+        let (min_x, max_x, min_y, max_y) = self.stars.iter().zip(self.velocity.iter()).fold(
+            (i64::MAX, i64::MIN, i64::MAX, i64::MIN),
+            |(min_x, max_x, min_y, max_y), (star, vel)| {
+                let x = star.x as i64 + vel.x as i64 * time;
+                let y = star.y as i64 + vel.y as i64 * time;
+                (min_x.min(x), max_x.max(x), min_y.min(y), max_y.max(y))
+            },
+        );
+        max_x - min_x + max_y - min_y
+    }
+
+    fn minimum_range_time(&self) -> Option<u32> {
+        // Each side of the box moves in a straight line until another star overtakes it, so the box
+        // can only shrink more and more slowly and then grow. The smallest is where it first stops
+        // shrinking, which a binary search finds without stepping through every second.
+        if self.stars.is_empty() == true {
+            return None;
+        }
+        let change = |time: i64| self.range_value_at(time + 1) - self.range_value_at(time);
+
+        // Find a time after the smallest, then search back for it
+        let mut high = 1;
+        while change(high) < 0 {
+            high *= 2;
+        }
+        let mut low = 0;
+        while low < high {
+            let middle = (low + high) / 2;
+            if change(middle) < 0 {
+                low = middle + 1;
             } else {
-                last_range = range;
+                high = middle;
             }
         }
-        count
+
+        // If it stays the same size for a while there's no one time to pick
+        if change(low) == 0 {
+            return None;
+        }
+        u32::try_from(low).ok()
+    }
+
+    fn step_until_minimum_range(&mut self) -> Option<u32> {
+        // Jumps straight to the smallest box and gives back how many seconds that took
+        let time = self.minimum_range_time()?;
+        self.step(time as i32);
+        Some(time)
     }
 }
 
@@ -128,7 +147,9 @@ impl fmt::Display for StarMap {
 #[aoc(day10, part2)]
 pub fn solve(input: &str) -> u32 {
     let mut star_map = StarMap::from_string(input);
-    let step_count = star_map.step_until_minimum_range();
+    let step_count = star_map
+        .step_until_minimum_range()
+        .expect("The stars don't settle on one smallest size");
     println!("Seconds until message appears: {}", step_count);
     assert_eq!(step_count, 10656);
     10656
@@ -321,6 +342,34 @@ position=<-3,  6> velocity=< 2, -1>";
 #...#..###"
                 .trim()
         );
-        assert_eq!(step_count, 3);
+        assert_eq!(step_count, Some(3));
+
+        // The same stars much further away
+        star_map.step(-5000);
+        assert_eq!(star_map.minimum_range_time(), Some(5000));
+    }
+
+    #[test]
+    fn test_no_minimum() {
+        // Moving together, so the box is always the same size
+        let input = "\
+position=< 0,  0> velocity=< 1,  1>
+position=< 5,  5> velocity=< 1,  1>";
+        let mut star_map = StarMap::from_string(input);
+        assert_eq!(star_map.step_until_minimum_range(), None);
+
+        // Passing each other, so the box is as small after 3 and 4 seconds
+        let input = "\
+position=<-3,  0> velocity=< 1,  0>
+position=< 4,  0> velocity=<-1,  0>";
+        let star_map = StarMap::from_string(input);
+        assert_eq!(star_map.minimum_range_time(), None);
+
+        // Until one of them stops
+        let input = "\
+position=<-3,  0> velocity=< 1,  0>
+position=< 4,  0> velocity=< 0,  0>";
+        let star_map = StarMap::from_string(input);
+        assert_eq!(star_map.minimum_range_time(), Some(7));
     }
 }
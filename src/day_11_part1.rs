@@ -53,18 +53,34 @@
 */
 
 use crate::common::Point;
-use std::collections::HashMap;
 
 struct Grid {
-    power: HashMap<Point, i32>,
-    size: u32,
+    sums: Vec<i32>, // Total power of every cell above and left of each point, both included
+    width: u32,
+    height: u32,
 }
 
 impl Grid {
-    fn new(size: u32) -> Self {
+    fn new(width: u32, height: u32, serial: i32) -> Self {
+        // Cells are numbered from 1, so row and column 0 of the sums are all 0. Each sum is the
+        // cell's own power plus the sums above and to the left, less the part they both cover.
+        let stride = width as usize + 1;
+        let mut sums = vec![0; stride * (height as usize + 1)];
+        for y in 1..=height as usize {
+            for x in 1..=width as usize {
+                let p = Point {
+                    x: x as i32,
+                    y: y as i32,
+                };
+                sums[y * stride + x] = Grid::power_level(p, serial) + sums[(y - 1) * stride + x]
+                    - sums[(y - 1) * stride + x - 1]
+                    + sums[y * stride + x - 1];
+            }
+        }
         Self {
-            power: HashMap::new(),
-            size,
+            sums,
+            width,
+            height,
         }
     }
 
@@ -78,41 +94,53 @@ impl Grid {
         power
     }
 
-    fn fuel_grid(&mut self, serial: i32) {
-        for x in 0..self.size {
-            for y in 0..self.size {
+    fn sum(&self, x: i32, y: i32) -> i32 {
+        self.sums[y as usize * (self.width as usize + 1) + x as usize]
+    }
+
+    fn total_power(&self, top_left: Point, width: u32, height: u32) -> i32 {
+        let (left, top) = (top_left.x - 1, top_left.y - 1);
+        let (right, bottom) = (left + width as i32, top + height as i32);
+        assert!(left >= 0 && top >= 0, "Off the grid: {}", top_left);
+        assert!(right <= self.width as i32 && bottom <= self.height as i32);
+        self.sum(right, bottom) - self.sum(left, bottom) - self.sum(right, top)
+            + self.sum(left, top)
+    }
+
+    fn best_rectangle(&self, width: u32, height: u32) -> Option<(Point, i32)> {
+        // The top left corner and total power of the rectangle with the most. Ties go to the first in
+        // reading order.
+        let mut best: Option<(Point, i32)> = None;
+        for y in 1..=(self.height + 1).checked_sub(height)? {
+            for x in 1..=(self.width + 1).checked_sub(width)? {
                 let p = Point {
                     x: x as i32,
                     y: y as i32,
                 };
-                let power = Grid::power_level(p, serial);
-                self.power.insert(p, power);
+                let power = self.total_power(p, width, height);
+                if best.is_none_or(|(_p, best_power)| power > best_power) {
+                    best = Some((p, power));
+                }
             }
         }
+        best
     }
 
-    fn total_power(&self, p: Point) -> i32 {
-        (0..3)
-            .flat_map(|x| {
-                (0..3).map(move |y| Point {
-                    x: p.x + x,
-                    y: p.y + y,
-                })
-            })
-            .map(|p| self.power[&p])
-            .sum()
+    fn best_square(&self, size: u32) -> Option<(Point, i32)> {
+        self.best_rectangle(size, size)
     }
 
-    fn max_power_point(&self) -> Point {
-        (0..(self.size - 2))
-            .flat_map(|x| {
-                (0..(self.size - 2)).map(move |y| Point {
-                    x: x as i32,
-                    y: y as i32,
-                })
-            })
-            .max_by_key(|&p| self.total_power(p))
-            .unwrap()
+    fn best_any_square(&self) -> Option<(Point, u32, i32)> {
+        // Ties go to the smallest
+        let mut best: Option<(Point, u32, i32)> = None;
+        for size in 1..=self.width.min(self.height) {
+            if let Some((p, power)) = self.best_square(size) {
+                if best.is_none_or(|(_p, _size, best_power)| power > best_power) {
+                    best = Some((p, size, power));
+                }
+            }
+        }
+        best
     }
 }
 
@@ -120,10 +148,8 @@ impl Grid {
 pub fn solve(input: &str) -> Point {
     let serial = input.trim().parse::<i32>().unwrap();
 
-    let mut grid = Grid::new(300);
-    grid.fuel_grid(serial);
-
-    let max_power_point = grid.max_power_point();
+    let grid = Grid::new(300, 300, serial);
+    let (max_power_point, _power) = grid.best_square(3).unwrap();
     println!("Largest total power: {}", max_power_point);
     assert_eq!(max_power_point, Point { x: 243, y: 68 });
     max_power_point
@@ -155,4 +181,29 @@ mod test {
         let power = Grid::power_level(point, serial);
         assert_eq!(power, 4);
     }
+
+    #[test]
+    fn test_best_square() {
+        let grid = Grid::new(300, 300, 18);
+        assert_eq!(grid.best_square(3), Some((Point { x: 33, y: 45 }, 29)));
+        let grid = Grid::new(300, 300, 42);
+        assert_eq!(grid.best_square(3), Some((Point { x: 21, y: 61 }, 30)));
+    }
+
+    #[test]
+    fn test_total_power() {
+        // Every rectangle matches adding up its cells
+        let grid = Grid::new(12, 9, 18);
+        for (p, width, height) in [(Point { x: 1, y: 1 }, 12, 9), (Point { x: 4, y: 2 }, 5, 7)] {
+            let expected: i32 = (0..width)
+                .flat_map(|x| (0..height).map(move |y| p + (x, y)))
+                .map(|cell| Grid::power_level(cell, 18))
+                .sum();
+            assert_eq!(grid.total_power(p, width as u32, height as u32), expected);
+        }
+
+        // Too big to fit
+        assert_eq!(grid.best_rectangle(13, 1), None);
+        assert_eq!(grid.best_rectangle(12, 9).unwrap().0, Point { x: 1, y: 1 });
+    }
 }
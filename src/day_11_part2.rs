@@ -12,18 +12,34 @@
 */
 
 use crate::common::Point;
-use std::collections::HashMap;
 
 struct Grid {
-    power: HashMap<(Point, u32), i32>, // Power level for each Point / size combination
-    size: u32,
+    sums: Vec<i32>, // Total power of every cell above and left of each point, both included
+    width: u32,
+    height: u32,
 }
 
 impl Grid {
-    fn new(size: u32) -> Self {
+    fn new(width: u32, height: u32, serial: i32) -> Self {
+        // Cells are numbered from 1, so row and column 0 of the sums are all 0. Each sum is the
+        // cell's own power plus the sums above and to the left, less the part they both cover.
+        let stride = width as usize + 1;
+        let mut sums = vec![0; stride * (height as usize + 1)];
+        for y in 1..=height as usize {
+            for x in 1..=width as usize {
+                let p = Point {
+                    x: x as i32,
+                    y: y as i32,
+                };
+                sums[y * stride + x] = Grid::power_level(p, serial) + sums[(y - 1) * stride + x]
+                    - sums[(y - 1) * stride + x - 1]
+                    + sums[y * stride + x - 1];
+            }
+        }
         Self {
-            power: HashMap::new(),
-            size,
+            sums,
+            width,
+            height,
         }
     }
 
@@ -37,90 +53,53 @@ impl Grid {
         power
     }
 
-    fn fuel_grid(&mut self, serial: i32) {
-        for x in 0..self.size {
-            for y in 0..self.size {
-                let p = Point {
-                    x: x as i32,
-                    y: y as i32,
-                };
-                let power = Grid::power_level(p, serial);
-                self.power.insert((p, 1), power);
-            }
-        }
+    fn sum(&self, x: i32, y: i32) -> i32 {
+        self.sums[y as usize * (self.width as usize + 1) + x as usize]
     }
 
-    fn total_power(&mut self, point: Point, size: u32) -> i32 {
-        // Memoize
-        if let Some(&power) = self.power.get(&(point, size)) {
-            return power;
-        }
-
-        assert!(size != 0);
-
-        let mut total_power = 0;
-
-        if size % 2 == 0 {
-            // Square can be evenly divided into 4 sub-squares
-            let subsquare = size / 2;
-            for x in 0..2 {
-                for y in 0..2 {
-                    let p = Point {
-                        x: point.x + x * subsquare as i32,
-                        y: point.y + y * subsquare as i32,
-                    };
-                    total_power += self.total_power(p, subsquare); // self.square_power[&(p, subsquare)];
-                }
-            }
-        } else {
-            // Square cannot be evenly divided. Split it into one large (size - 1) and many small (1).
-
-            // The large square
-            total_power += self.power[&(point, size - 1)];
+    fn total_power(&self, top_left: Point, width: u32, height: u32) -> i32 {
+        let (left, top) = (top_left.x - 1, top_left.y - 1);
+        let (right, bottom) = (left + width as i32, top + height as i32);
+        assert!(left >= 0 && top >= 0, "Off the grid: {}", top_left);
+        assert!(right <= self.width as i32 && bottom <= self.height as i32);
+        self.sum(right, bottom) - self.sum(left, bottom) - self.sum(right, top)
+            + self.sum(left, top)
+    }
 
-            // Many small squares. Bottom row, then right row, being careful of the point that is in both.
-            for x in 0..size {
+    fn best_rectangle(&self, width: u32, height: u32) -> Option<(Point, i32)> {
+        // The top left corner and total power of the rectangle with the most. Ties go to the first in
+        // reading order.
+        let mut best: Option<(Point, i32)> = None;
+        for y in 1..=(self.height + 1).checked_sub(height)? {
+            for x in 1..=(self.width + 1).checked_sub(width)? {
                 let p = Point {
-                    x: point.x + x as i32,
-                    y: point.y + size as i32 - 1,
-                };
-                total_power += self.total_power(p, 1);
-            }
-            for y in 0..(size - 1) {
-                let p = Point {
-                    x: point.x + size as i32 - 1,
-                    y: point.y + y as i32,
+                    x: x as i32,
+                    y: y as i32,
                 };
-                total_power += self.total_power(p, 1);
+                let power = self.total_power(p, width, height);
+                if best.is_none_or(|(_p, best_power)| power > best_power) {
+                    best = Some((p, power));
+                }
             }
         }
+        best
+    }
 
-        self.power.insert((point, size), total_power);
-        total_power
+    fn best_square(&self, size: u32) -> Option<(Point, i32)> {
+        self.best_rectangle(size, size)
     }
 
-    fn max_power_point(&mut self) -> (Point, u32) {
-        let mut max_power = None;
-        let mut max_point = None;
-        let mut max_size = None;
-        for size in 2..=self.size {
-            //println!("Size: {}", size);
-            for x in 0..(self.size - size) {
-                for y in 0..(self.size - size) {
-                    let p = Point {
-                        x: x as i32,
-                        y: y as i32,
-                    };
-                    let power = self.total_power(p, size);
-                    if max_power.is_none() || power > max_power.unwrap() {
-                        max_power = Some(power);
-                        max_point = Some(p);
-                        max_size = Some(size);
-                    }
+    fn best_any_square(&self) -> Option<(Point, u32, i32)> {
+        // Ties go to the smallest
+        let mut best: Option<(Point, u32, i32)> = None;
+        for size in 1..=self.width.min(self.height) {
+            if let Some((p, power)) = self.best_square(size) {
+                if best.is_none_or(|(_p, _size, best_power)| power > best_power) {
+                    best = Some((p, size, power));
                 }
             }
         }
-        (max_point.unwrap(), max_size.unwrap())
+        best
     }
 }
 
@@ -128,10 +107,8 @@ impl Grid {
 pub fn solve(input: &str) -> String {
     let serial = input.trim().parse::<i32>().unwrap();
 
-    let mut grid = Grid::new(300);
-    grid.fuel_grid(serial);
-
-    let (max_power_point, max_power_size) = grid.max_power_point();
+    let grid = Grid::new(300, 300, serial);
+    let (max_power_point, max_power_size, _power) = grid.best_any_square().unwrap();
     println!(
         "Largest total power: {}, {}",
         max_power_point, max_power_size
@@ -171,4 +148,18 @@ mod test {
         let power = Grid::power_level(point, serial);
         assert_eq!(power, 4);
     }
+
+    #[test]
+    fn test_best_any_square() {
+        let grid = Grid::new(300, 300, 18);
+        assert_eq!(
+            grid.best_any_square(),
+            Some((Point { x: 90, y: 269 }, 16, 113))
+        );
+        let grid = Grid::new(300, 300, 42);
+        assert_eq!(
+            grid.best_any_square(),
+            Some((Point { x: 232, y: 251 }, 12, 119))
+        );
+    }
 }
@@ -65,6 +65,7 @@
     After 20 generations, what is the sum of the numbers of all pots which contain a plant?
 */
 
+use crate::common::BitAutomaton;
 use nom::{
     bytes::complete::tag,
     character::complete::{line_ending, one_of},
@@ -183,6 +184,10 @@ impl Tunnel {
         Ok((input, Self { pots, rules }))
     }
 
+    fn to_automaton(&self) -> BitAutomaton {
+        BitAutomaton::new(&self.pots.pots, self.pots.first_pot as i64, &self.rules)
+    }
+
     fn rule_id_from_string(input: &str) -> i32 {
         let mut output = 0;
         for c in input.chars() {
@@ -211,7 +216,7 @@ impl Tunnel {
             // However, we skip the first 4 since they will always be false and rule_id can start at 0 by definition.
             // So we only need 4 extra pots total.
             let (pots, slice_offset) = current.trim();
-            for &pot in pots.iter().chain(std::iter::repeat(&false).take(4)) {
+            for &pot in pots.iter().chain(std::iter::repeat_n(&false, 4)) {
                 rule_id = (rule_id << 1) & 0x1F; // Keep only 5 bits
                 if pot == true {
                     // The new pot is the rightmost (2 from the center)
@@ -240,6 +245,19 @@ pub fn solve(input: &str) -> i32 {
     sum_pots
 }
 
+#[aoc(day12, part1, Packed)]
+pub fn solve_packed(input: &str) -> i64 {
+    let tunnel = Tunnel::from_string(input);
+    let mut automaton = tunnel.to_automaton();
+    for _ in 0..20 {
+        automaton.step();
+    }
+
+    let sum_pots = automaton.sum();
+    assert_eq!(sum_pots, 3890);
+    sum_pots
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -317,4 +335,17 @@ initial state: #..#.#..##......###...###
         tunnel.step(20);
         assert_eq!(tunnel.pots.sum(), 325);
     }
+
+    #[test]
+    fn test_packed() {
+        let input = include_str!("../input/2018/day12.txt");
+        let mut tunnel = Tunnel::from_string(input);
+        let mut automaton = tunnel.to_automaton();
+        for _ in 0..200 {
+            tunnel.step(1);
+            automaton.step();
+            assert_eq!(automaton.to_string(), tunnel.pots.to_string());
+            assert_eq!(automaton.sum(), tunnel.pots.sum() as i64);
+        }
+    }
 }
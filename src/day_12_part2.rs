@@ -5,6 +5,10 @@
     After fifty billion (50000000000) generations, what is the sum of the numbers of all pots which contain a plant?
 */
 
+use crate::common::find_cycle;
+use crate::common::run_to;
+use crate::common::Cyclic;
+use crate::common::Simulation;
 use nom::{
     bytes::complete::tag,
     character::complete::{line_ending, one_of},
@@ -22,13 +26,6 @@ struct Pots {
 }
 
 impl Pots {
-    fn new() -> Self {
-        Self {
-            pots: Vec::new(),
-            first_pot: 0,
-        }
-    }
-
     fn from_string(input: &str) -> Self {
         let pots: Vec<bool> = input.chars().map(|c| c == '#').collect();
 
@@ -58,6 +55,10 @@ impl Pots {
         (&self.pots[begin..end], begin)
     }
 
+    fn count(&self) -> i64 {
+        self.pots.iter().filter(|&&pot| pot == true).count() as i64
+    }
+
     fn sum(&self) -> i64 {
         self.pots
             .iter()
@@ -83,6 +84,7 @@ impl fmt::Display for Pots {
     }
 }
 
+#[derive(Clone)]
 struct Tunnel {
     pots: Pots,
     rules: Vec<bool>,
@@ -135,50 +137,83 @@ impl Tunnel {
     }
 
     fn step(&mut self, count: u64) {
-        // Use double buffering to reduce memory allocation overhead
-        let mut current: &mut Pots = &mut self.pots.clone();
-        let mut next: &mut Pots = &mut Pots::new();
-
-        for _ in 0..count {
-            // For each pot that could be a plant, calculate which rule ID to use and then apply it.
-            next.pots.clear();
-
-            // ID always starts at 0 by definition (first pot we check is ....# and the last bit is added in the loop)
-            let mut rule_id = 0;
-
-            // We will iterate over all existing pots but need to account for 2 pots before and 2 after.
-            // Each of those pots looks 2 left and 2 right so we need to produce 8 additional pots in the iterator.
-            // However, we skip the first 4 since they will always be false and rule_id can start at 0 by definition.
-            // So we only need 4 extra pots total.
-            let (pots, slice_offset) = current.trim();
-            for &pot in pots.iter().chain(std::iter::repeat(&false).take(4)) {
+        // The plants settle into a pattern that only moves along, so skip ahead once it's found
+        run_to(self, count);
+    }
+
+    fn sum_after(&self, count: u64) -> i64 {
+        // Once the pattern only moves along, every plant moves the same distance each period, so the
+        // sum goes up by that much for each plant. Only simulate to the last period before the count.
+        let (repeats, period, shift) = match find_cycle(self, count) {
+            Some(cycle) => (
+                (count - cycle.start) / cycle.period,
+                cycle.period,
+                cycle.shift,
+            ),
+            None => (0, 0, 0),
+        };
+
+        let mut tunnel = self.clone();
+        for _ in 0..count - repeats * period {
+            tunnel.tick();
+        }
+        tunnel.pots.sum() + repeats as i64 * shift * tunnel.pots.count()
+    }
+}
+
+impl Simulation for Tunnel {
+    fn tick(&mut self) {
+        // For each pot that could be a plant, calculate which rule ID to use and then apply it.
+        // ID always starts at 0 by definition (first pot we check is ....# and the last bit is added in the loop)
+        let mut rule_id = 0;
+
+        // We will iterate over all existing pots but need to account for 2 pots before and 2 after.
+        // Each of those pots looks 2 left and 2 right so we need to produce 8 additional pots in the iterator.
+        // However, we skip the first 4 since they will always be false and rule_id can start at 0 by definition.
+        // So we only need 4 extra pots total.
+        let (pots, slice_offset) = self.pots.trim();
+        let next: Vec<bool> = pots
+            .iter()
+            .chain(std::iter::repeat_n(&false, 4))
+            .map(|&pot| {
                 rule_id = (rule_id << 1) & 0x1F; // Keep only 5 bits
                 if pot == true {
                     // The new pot is the rightmost (2 from the center)
                     rule_id |= 1;
                 }
-                next.pots.push(self.rules[rule_id]);
-            }
+                self.rules[rule_id]
+            })
+            .collect();
 
-            // Record the change in the first pot offset, if any. Swap buffers.
-            next.first_pot = current.first_pot + slice_offset as i64 - 2;
-            std::mem::swap(&mut current, &mut next); // Swap the references without moving the memory
-        }
-        self.pots = current.clone();
+        // Record the change in the first pot offset, if any
+        self.pots = Pots {
+            pots: next,
+            first_pot: self.pots.first_pot + slice_offset as i64 - 2,
+        };
+    }
+}
+
+impl Cyclic for Tunnel {
+    type Key = Vec<bool>;
+
+    fn key(&self) -> Vec<bool> {
+        // Only the plants, not the empty pots around them
+        self.pots.trim().0.to_vec()
+    }
+
+    fn offset(&self) -> i64 {
+        self.pots.first_pot + self.pots.trim().1 as i64
+    }
+
+    fn translate(&mut self, by: i64) {
+        self.pots.first_pot += by;
     }
 }
 
-// Currently this takes about 12 hours to run.
-// Ideas for future improvement:
-// * Make rules process more than 1 pot at a time, by increasing the 'size' of the rule. E.g. instead of 5 bits,
-//   make it 15 and it would process 11 pots at a time. This approach is limited by memory usage.
-// * Use a more compact data structure than Vec<bool> for the pots such as packed bits
 #[aoc(day12, part2)]
 pub fn solve(input: &str) -> i64 {
-    let mut tunnel = Tunnel::from_string(input);
-    tunnel.step(50_000_000_000);
-
-    let sum_pots = tunnel.pots.sum();
+    let tunnel = Tunnel::from_string(input);
+    let sum_pots = tunnel.sum_after(50_000_000_000);
     println!("Sum of pots: {}", sum_pots);
     assert_eq!(sum_pots, 4800000001087);
     sum_pots
@@ -187,6 +222,7 @@ pub fn solve(input: &str) -> i64 {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::common::Cycle;
 
     #[test]
     fn test_step() {
@@ -261,4 +297,45 @@ initial state: #..#.#..##......###...###
         tunnel.step(20);
         assert_eq!(tunnel.pots.sum(), 325);
     }
+
+    #[test]
+    fn test_extrapolate() {
+        let input = "\
+initial state: #..#.#..##......###...###
+
+...## => #
+..#.. => #
+.#... => #
+.#.#. => #
+.#.## => #
+.##.. => #
+.#### => #
+#.#.# => #
+#.### => #
+##.#. => #
+##.## => #
+###.. => #
+###.# => #
+####. => #";
+        let tunnel = Tunnel::from_string(input);
+        assert_eq!(
+            find_cycle(&tunnel, 1000),
+            Some(Cycle {
+                start: 86,
+                period: 1,
+                shift: 1
+            })
+        );
+
+        // Skipping ahead must give the same as simulating every generation, either side of the cycle starting
+        let mut brute_force = tunnel.clone();
+        for count in 0..200 {
+            let mut skipped = tunnel.clone();
+            skipped.step(count);
+            assert_eq!(skipped.pots.to_string(), brute_force.pots.to_string());
+            assert_eq!(skipped.offset(), brute_force.offset());
+            assert_eq!(tunnel.sum_after(count), brute_force.pots.sum());
+            brute_force.tick();
+        }
+    }
 }
@@ -78,428 +78,103 @@
     After increasing the Elves' attack power until it is just barely enough for them to win without any Elves dying, what is the outcome of the combat described in your puzzle input?
 */
 
-use crate::common::Point;
-use std::collections::BTreeMap;
-use std::collections::BTreeSet;
-use std::fmt;
-use std::fmt::Write;
+use crate::common::combat::BattleMap;
+use std::thread;
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
-enum Tile {
-    Empty,
-    Wall,
-    Goblin(u32),
-    Elf(u32),
-}
-
-impl Tile {
-    fn from_char(c: char) -> Self {
-        match c {
-            '.' => Self::Empty,
-            '#' => Self::Wall,
-            'G' => Self::Goblin(200),
-            'E' => Self::Elf(200),
-            _ => panic!("Unknown tile: {}", c),
-        }
-    }
-
-    fn to_char(&self) -> char {
-        match *self {
-            Self::Empty => '.',
-            Self::Wall => '#',
-            Self::Goblin(_) => 'G',
-            Self::Elf(_) => 'E',
-        }
-    }
-}
+const ELF: &str = "Elf";
 
-impl fmt::Display for Tile {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.to_char())?;
-        match self {
-            Self::Empty => (),
-            Self::Wall => (),
-            Self::Goblin(x) => write!(f, "({})", x)?,
-            Self::Elf(x) => write!(f, "({})", x)?,
-        };
-        Ok(())
-    }
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Outcome {
+    ElfDied { round: u32 },
+    Won { score: u32 },
 }
 
-#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
-struct Path {
-    first: Point,
-    last: Point,
-    length: usize,
+fn try_power(battle_map: &BattleMap, elf_power: u32) -> (BattleMap, Outcome) {
+    let mut parallel_reality = battle_map.clone();
+    parallel_reality.set_attack(ELF, elf_power);
+    let outcome = match parallel_reality.battle_without_losses(ELF) {
+        Some(score) => Outcome::Won { score },
+        None => Outcome::ElfDied {
+            round: parallel_reality.round(),
+        },
+    };
+    (parallel_reality, outcome)
 }
 
-#[derive(Clone)]
-struct BattleMap {
-    tiles: BTreeMap<Point, Tile>,
-    goblin_atk: u32,
-    elf_atk: u32,
+fn powers_to_try(low: u32, high: Option<u32>, count: u32) -> Vec<u32> {
+    // Winning without losses only gets easier with more attack, so every power below one that lost
+    // loses too and only powers from low up to the lowest win so far (high) need trying. Until
+    // there's a win the highest power that lost is doubled, after that the gap is split evenly.
+    let mut powers: Vec<u32> = match high {
+        None => (1..=count)
+            .map(|i| (low - 1).saturating_mul(2u32.saturating_pow(i)))
+            .collect(),
+        Some(high) if high - low <= count => (low..high).collect(),
+        Some(high) => (1..=count)
+            .map(|i| low + (high - low) * i / (count + 1))
+            .collect(),
+    };
+    powers.dedup();
+    powers
 }
 
-impl BattleMap {
-    fn from_string(input: &str) -> Self {
-        let mut tiles = BTreeMap::new();
-        let mut p = Point::new();
-        for line in input.trim().lines() {
-            for c in line.chars() {
-                tiles.insert(p, Tile::from_char(c));
-                p.x += 1;
-            }
-            p.x = 0;
-            p.y += 1;
-        }
-
-        Self {
-            tiles,
-            goblin_atk: 3,
-            elf_atk: 3,
-        }
-    }
-
-    fn get_range(&self) -> ((i32, i32), (i32, i32)) {
-        let mut tiles_iter = self.tiles.iter();
-        if let Some((point, _tile)) = tiles_iter.next() {
-            tiles_iter.fold(
-                ((point.x, point.x), (point.y, point.y)),
-                |(acc_x, acc_y), (p, _)| {
-                    (
-                        (acc_x.0.min(p.x), acc_x.1.max(p.x)),
-                        (acc_y.0.min(p.y), acc_y.1.max(p.y)),
-                    )
-                },
-            )
-        } else {
-            ((0, 0), (0, 0))
-        }
-    }
-
-    fn to_string(&self, with_details: bool) -> String {
-        let mut output = String::new();
-        let range = self.get_range();
-        for y in (range.1).0..=(range.1).1 {
-            let mut unit_strings = Vec::new();
-            for x in (range.0).0..=(range.0).1 {
-                if let Some(tile) = self.tiles.get(&Point { x, y }) {
-                    write!(output, "{}", tile.to_char()).unwrap();
-                    if with_details == true {
-                        match tile {
-                            Tile::Goblin(_) | Tile::Elf(_) => unit_strings.push(tile.to_string()),
-                            _ => (),
-                        }
-                    }
-                } else {
-                    write!(output, ".").unwrap();
-                }
-            }
-            if with_details == true && unit_strings.is_empty() == false {
-                write!(output, "   {}", unit_strings.join(", ")).unwrap();
-            }
-            writeln!(output).unwrap();
-        }
-        output
-    }
-
-    // Returns in reading order
-    fn identify_units(&self) -> Vec<Point> {
-        let mut units: Vec<Point> = self
-            .tiles
-            .iter()
-            .filter(|&(_point, tile)| matches!(tile, Tile::Goblin(_) | Tile::Elf(_)))
-            .map(|(&point, &_tile)| point)
-            .collect();
-        units.sort_by(Point::cmp_y_x);
-        units
-    }
-
-    fn identify_enemies(&self, am_goblin: bool) -> Vec<Point> {
-        let units: Vec<Point> = self
-            .tiles
-            .iter()
-            .filter(|&(_point, tile)| match tile {
-                Tile::Goblin(_) => am_goblin == false,
-                Tile::Elf(_) => am_goblin == true,
-                _ => false,
-            })
-            .map(|(&point, &_tile)| point)
-            .collect();
-        units
-    }
-
-    fn identify_adjacent_empty(&self, unit: &Point) -> Vec<Point> {
-        unit.orthogonals()
-            .into_iter()
-            .filter(|&p| self.tiles.get(&p) == Some(&Tile::Empty))
-            .collect()
-    }
-
-    fn identify_adjacent_enemies(&self, unit: &Point, am_goblin: bool) -> Vec<Point> {
-        unit.orthogonals()
-            .into_iter()
-            .filter(|&point| match self.tiles.get(&point) {
-                Some(Tile::Goblin(_)) => am_goblin == false,
-                Some(Tile::Elf(_)) => am_goblin == true,
-                _ => false,
-            })
-            .collect()
-    }
-
-    fn find_paths(&self, from: &Point, to: &[Point]) -> Vec<Path> {
-        let mut paths: BTreeSet<Path> = BTreeSet::new();
-        let mut shortest_path = None;
-        let to_set: BTreeSet<Point> = to.iter().cloned().collect();
-
-        let starts: Vec<Point> = self.identify_adjacent_empty(from);
-        for start in starts {
-            let mut visited: BTreeSet<Point> = BTreeSet::new();
-            visited.insert(*from);
-            visited.insert(start);
-
-            let mut frontier: Vec<Point> = vec![start];
-            let mut distance = 0;
-            while frontier.is_empty() == false {
-                distance += 1;
-                if let Some(shortest) = shortest_path {
-                    if distance > shortest {
-                        break; // As an optimization, quit searching for paths if already exceeded the shortest we've seen
-                    }
-                }
-
-                let mut found_a_target = false;
-                for frontier_point in frontier.drain(..).collect::<Vec<Point>>() {
-                    if to_set.contains(&frontier_point) == true {
-                        found_a_target = true;
-                        let path = Path {
-                            first: start,
-                            last: frontier_point,
-                            length: distance,
-                        };
-                        paths.insert(path);
-                    }
-                    visited.insert(frontier_point);
-
-                    let next: Vec<Point> = self
-                        .identify_adjacent_empty(&frontier_point)
-                        .into_iter()
-                        .filter(|point| visited.get(&point) == None)
-                        .collect();
-                    for &n in &next {
-                        if frontier.contains(&n) == false {
-                            frontier.push(n);
-                        }
-                    }
-                }
-
-                // As an optimization we quit searching once we found any target (finishing the current frontier). It is
-                // guaranteed that we won't need any longer paths since the next step after finding the paths would be
-                // culling all but the shortest ones.
-                if found_a_target == true {
-                    if let Some(shortest) = shortest_path {
-                        if distance < shortest {
-                            shortest_path = Some(distance);
-                        }
-                    } else {
-                        shortest_path = Some(distance);
-                    }
-                    break;
-                }
-            }
-        }
-
-        // Convert from BTreeSet to Vec, removing all but the shortest paths
-        let paths_vec: Vec<Path> = paths
-            .iter()
-            .filter(|path| path.length == shortest_path.unwrap())
-            .cloned()
-            .collect();
-        paths_vec
-    }
-
-    fn tick(&mut self) -> bool {
-        let units = self.identify_units();
-        for unit in units {
-            let mut unit_location = unit;
-
-            // Is this unit a goblin or an elf?
-            let am_goblin = match self.tiles.get(&unit_location) {
-                Some(Tile::Goblin(_)) => true,
-                Some(Tile::Elf(_)) => false,
-                _ => continue, // Unit was killed in an earlier iteration
-            };
-
-            // If there are no adjacent enemies, move towards one
-            let adjacent_enemies = self.identify_adjacent_enemies(&unit_location, am_goblin);
-            if adjacent_enemies.is_empty() == true {
-                // Find all spaces adjacent to all enemies on the map
-                let enemies = self.identify_enemies(am_goblin);
-                if enemies.is_empty() == true {
-                    // No more enemies, end immediately
-                    return false;
-                }
-                let mut adjacents: Vec<Point> = enemies
-                    .into_iter()
-                    .flat_map(|enemy| self.identify_adjacent_empty(&enemy))
-                    .collect();
-                adjacents.sort_by(Point::cmp_y_x);
-                adjacents.dedup();
-                if adjacents.is_empty() == true {
-                    // No possible targets
-                    continue;
-                }
-
-                // Find the shortest path(s) to each reachable space
-                let mut paths: Vec<Path> = self.find_paths(&unit_location, &adjacents);
-                paths.sort_by_key(|path| path.last); // The paths are usually, but not always, in reading order by target. Ensure it by sorting.
-                if paths.is_empty() == true {
-                    // No paths to a target
-                    continue;
-                }
-
-                // Keep only paths to the first target in reading order
-                let target = paths[0].last;
-                let paths_to_target: Vec<Path> = paths
-                    .into_iter()
-                    .filter(|path| path.last == target)
-                    .collect();
-
-                // Take the first step in reading order
-                let mut steps: Vec<Point> =
-                    paths_to_target.into_iter().map(|path| path.first).collect();
-                steps.sort_by(Point::cmp_y_x);
-                unit_location = steps[0];
-
-                // Step by overwriting current space and new space
-                let unit_data = self.tiles.insert(unit, Tile::Empty).unwrap();
-                let previous = self.tiles.insert(unit_location, unit_data).unwrap();
-                assert_eq!(previous, Tile::Empty);
-            }
-
-            // Check again if there are adjacent enemies (we may have moved). Attack if possible.
-            let enemies_can_attack: Vec<Point> =
-                self.identify_adjacent_enemies(&unit_location, am_goblin);
-            if enemies_can_attack.is_empty() == false {
-                // Find enemies with lowest HP
-                let least_hp = enemies_can_attack
-                    .iter()
-                    .map(|&enemy| match self.tiles.get(&enemy) {
-                        Some(&Tile::Goblin(x)) => x,
-                        Some(&Tile::Elf(x)) => x,
-                        _ => panic!("Unexpected tile"),
-                    })
-                    .min()
-                    .unwrap();
-                let mut enemies_least_hp: Vec<Point> = enemies_can_attack
-                    .into_iter()
-                    .filter(|&enemy| match self.tiles.get(&enemy) {
-                        Some(&Tile::Goblin(x)) => x == least_hp,
-                        Some(&Tile::Elf(x)) => x == least_hp,
-                        _ => panic!("Unexpected tile"),
+fn power_up_elves(battle_map: &mut BattleMap) -> (u32, u32) {
+    // Tries as many powers at once as there are threads to run them
+    let threads = thread::available_parallelism().map_or(1, |n| n.get()) as u32;
+    let mut low = 4; // One more than default of 3
+    let mut best: Option<(u32, BattleMap, u32)> = None; // Lowest power that won, the battle and its score
+    let mut attempts: Vec<(u32, Outcome)> = Vec::new();
+    while best.as_ref().is_none_or(|&(power, _, _)| power > low) {
+        let powers = powers_to_try(low, best.as_ref().map(|&(power, _, _)| power), threads);
+        let results: Vec<(u32, BattleMap, Outcome)> = thread::scope(|scope| {
+            let battle_map = &*battle_map;
+            let handles: Vec<_> = powers
+                .iter()
+                .map(|&power| {
+                    scope.spawn(move || {
+                        let (parallel_reality, outcome) = try_power(battle_map, power);
+                        (power, parallel_reality, outcome)
                     })
-                    .collect();
-                enemies_least_hp.sort_by(Point::cmp_y_x);
-
-                // Attack first enemy in the list
-                if enemies_least_hp.is_empty() == false {
-                    let enemy = enemies_least_hp[0];
-                    let enemy_adjusted = match self.tiles.get(&enemy) {
-                        Some(&Tile::Goblin(x)) => {
-                            if x <= self.elf_atk {
-                                Tile::Empty // Dead goblin
-                            } else {
-                                Tile::Goblin(x - self.elf_atk)
-                            }
-                        }
-                        Some(&Tile::Elf(x)) => {
-                            if x <= self.goblin_atk {
-                                Tile::Empty // Dead elf
-                            } else {
-                                Tile::Elf(x - self.goblin_atk)
-                            }
-                        }
-                        _ => panic!("Unexpected tile"),
-                    };
-                    self.tiles.insert(enemy, enemy_adjusted);
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+
+        for (power, parallel_reality, outcome) in results {
+            attempts.push((power, outcome));
+            match outcome {
+                Outcome::ElfDied { .. } => low = low.max(power + 1),
+                Outcome::Won { score } => {
+                    if best
+                        .as_ref()
+                        .is_none_or(|&(best_power, _, _)| power < best_power)
+                    {
+                        best = Some((power, parallel_reality, score));
+                    }
                 }
             }
         }
-
-        true
-    }
-
-    fn count_goblins(&self) -> u32 {
-        self.tiles
-            .iter()
-            .filter(|&(_point, tile)| matches!(tile, Tile::Goblin(_)))
-            .count() as u32
-    }
-
-    fn count_elves(&self) -> u32 {
-        self.tiles
-            .iter()
-            .filter(|&(_point, tile)| matches!(tile, Tile::Elf(_)))
-            .count() as u32
     }
 
-    fn calculate_score(&self, rounds: u32) -> u32 {
-        let total_hit_points: u32 = self
-            .tiles
-            .iter()
-            .map(|(_point, tile)| match tile {
-                &Tile::Goblin(x) | &Tile::Elf(x) => x,
-                _ => 0,
-            })
-            .sum();
-        rounds * total_hit_points
-    }
-
-    fn battle(&mut self) -> u32 {
-        let mut rounds = 0;
-        loop {
-            if self.count_goblins() == 0 || self.count_elves() == 0 {
-                // Simulation is over. Calculate final score.
-                return self.calculate_score(rounds);
-            }
-
-            if self.tick() == true {
-                rounds += 1;
+    attempts.sort_by_key(|&(power, _outcome)| power);
+    for (power, outcome) in attempts {
+        match outcome {
+            Outcome::ElfDied { round } => {
+                println!("Attack {}: an elf died in round {}", power, round)
             }
+            Outcome::Won { score } => println!("Attack {}: no elves died, score {}", power, score),
         }
     }
 
-    fn power_up_elves(&mut self) -> (u32, u32) {
-        let mut elf_power = 4; // One more than default of 3
-        loop {
-            let mut parallel_reality = self.clone();
-            parallel_reality.elf_atk = elf_power;
-
-            let start_elf_count = parallel_reality.count_elves();
-            let score = parallel_reality.battle();
-            let end_elf_count = parallel_reality.count_elves();
-
-            if start_elf_count == end_elf_count {
-                // No elves died
-                *self = parallel_reality; // Not strictly necessary to copy this over, but it makes it possible to externally test the result
-                return (score, elf_power);
-            }
-
-            elf_power += 1;
-        }
-    }
-}
-
-impl fmt::Display for BattleMap {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.to_string(false))
-    }
+    let (power, parallel_reality, score) = best.unwrap();
+    *battle_map = parallel_reality; // Not strictly necessary to copy this over, but it makes it possible to externally test the result
+    (score, power)
 }
 
 #[aoc(day15, part2)]
 pub fn solve(input: &str) -> u32 {
     let mut battle_map = BattleMap::from_string(input);
-    let (score, _power) = battle_map.power_up_elves();
+    let (score, _power) = power_up_elves(&mut battle_map);
     println!("Score: {}", score);
     assert_eq!(score, 41804);
     score
@@ -508,6 +183,7 @@ pub fn solve(input: &str) -> u32 {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::common::Simulation;
 
     #[test]
     fn test_tick1() {
@@ -820,6 +496,45 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_powers_to_try() {
+        assert_eq!(powers_to_try(4, None, 4), [6, 12, 24, 48]);
+        assert_eq!(powers_to_try(13, None, 1), [24]);
+        assert_eq!(powers_to_try(33, Some(64), 1), [48]);
+        assert_eq!(powers_to_try(33, Some(64), 3), [40, 48, 56]);
+        assert_eq!(powers_to_try(33, Some(36), 3), [33, 34, 35]);
+        assert_eq!(
+            powers_to_try(u32::MAX / 2, None, 4),
+            [u32::MAX - 3, u32::MAX]
+        );
+    }
+
+    #[test]
+    fn test_try_power() {
+        // Gives up straight after the turn an elf dies in, without finishing the round
+        let battle_map = BattleMap::from_string(
+            "
+#######
+#.G...#
+#...EG#
+#.#.#G#
+#..G#E#
+#.....#
+#######",
+        );
+        let (lost, outcome) = try_power(&battle_map, 3);
+        assert_eq!(outcome, Outcome::ElfDied { round: 23 });
+        let last = lost.log().turns().last().unwrap();
+        assert_eq!(lost.count(ELF), battle_map.count(ELF) - 1);
+        assert_eq!(last.round, lost.round());
+
+        let mut full = battle_map.clone();
+        full.battle();
+        let turns = lost.log().turns().len();
+        assert_eq!(lost.log().turns(), &full.log().turns()[..turns]);
+        assert!(full.log().round(lost.round()).count() > lost.log().round(lost.round()).count());
+    }
+
     #[test]
     fn test_power_up_elves() {
         let inputs_results_scores_power: Vec<(&str, &str, u32, u32)> = vec![
@@ -931,7 +646,7 @@ mod test {
 
         for (input, result, score, power) in inputs_results_scores_power {
             let mut battle_map = BattleMap::from_string(input);
-            let (battle_score, battle_power) = battle_map.power_up_elves();
+            let (battle_score, battle_power) = power_up_elves(&mut battle_map);
             assert_eq!(battle_score, score);
             assert_eq!(battle_power, power);
             assert_eq!(battle_map.to_string(true).trim(), result.trim());
@@ -61,331 +61,17 @@
     Ignoring the opcode numbers, how many samples in your puzzle input behave like three or more opcodes?
 */
 
+use crate::common::elfcode;
 use nom::{
     bytes::complete::tag,
-    character::complete::{char, digit1, multispace0},
-    combinator::map_res,
+    character::complete::multispace0,
     multi::many1,
-    sequence::{delimited, pair, preceded, terminated, tuple},
+    sequence::{pair, preceded, tuple},
     IResult,
 };
-use std::ops::{Index, IndexMut};
 
-#[derive(Clone, Eq, PartialEq)]
-struct State([u32; 4]);
-
-impl State {
-    fn parser(input: &str) -> IResult<&str, Self> {
-        let (input, (a, b, c, d)) = tuple((
-            preceded(
-                pair(multispace0, char('[')),
-                map_res(digit1, |x: &str| x.parse::<u32>()),
-            ),
-            preceded(tag(", "), map_res(digit1, |x: &str| x.parse::<u32>())),
-            preceded(tag(", "), map_res(digit1, |x: &str| x.parse::<u32>())),
-            delimited(
-                tag(", "),
-                map_res(digit1, |x: &str| x.parse::<u32>()),
-                char(']'),
-            ),
-        ))(input)?;
-
-        Ok((input, Self([a, b, c, d])))
-    }
-}
-
-impl Index<u32> for State {
-    type Output = u32;
-
-    fn index(&self, index: u32) -> &Self::Output {
-        &self.0[index as usize]
-    }
-}
-
-impl IndexMut<u32> for State {
-    fn index_mut(&mut self, index: u32) -> &mut Self::Output {
-        &mut self.0[index as usize]
-    }
-}
-
-#[derive(Debug)]
-enum Error {
-    Opcode,
-    Functioncode,
-    InputA,
-    InputB,
-    OutputC,
-}
-
-struct Instruction {
-    opcode: u8,
-    input_a: u32,
-    input_b: u32,
-    output_c: u32,
-}
-
-impl Instruction {
-    fn from_string(input: &str) -> Self {
-        Self::parser(input).unwrap().1
-    }
-
-    fn parser(input: &str) -> IResult<&str, Self> {
-        let (input, (opcode, input_a, input_b, output_c)) = tuple((
-            delimited(
-                multispace0,
-                map_res(digit1, |x: &str| x.parse::<u8>()),
-                char(' '),
-            ),
-            terminated(map_res(digit1, |x: &str| x.parse::<u32>()), char(' ')),
-            terminated(map_res(digit1, |x: &str| x.parse::<u32>()), char(' ')),
-            map_res(digit1, |x: &str| x.parse::<u32>()),
-        ))(input)?;
-
-        Ok((
-            input,
-            Self {
-                opcode,
-                input_a,
-                input_b,
-                output_c,
-            },
-        ))
-    }
-
-    fn validate_opcode(&self) -> Result<(), Error> {
-        if self.opcode > 15 {
-            Err(Error::Opcode)
-        } else {
-            Ok(())
-        }
-    }
-
-    fn validate_reg_a(&self) -> Result<(), Error> {
-        if self.input_a > 3 {
-            Err(Error::InputA)
-        } else {
-            Ok(())
-        }
-    }
-
-    fn validate_reg_b(&self) -> Result<(), Error> {
-        if self.input_b > 3 {
-            Err(Error::InputB)
-        } else {
-            Ok(())
-        }
-    }
-
-    fn validate_reg_c(&self) -> Result<(), Error> {
-        if self.output_c > 3 {
-            Err(Error::OutputC)
-        } else {
-            Ok(())
-        }
-    }
-
-    fn dispatch(&self, input: State, opcode_lookup: &[u8; 16]) -> Result<State, Error> {
-        self.validate_opcode()?;
-        let function_code = opcode_lookup[self.opcode as usize];
-        self.dispatch_as(input, function_code)
-    }
-
-    fn dispatch_as(&self, input: State, function_code: u8) -> Result<State, Error> {
-        match function_code {
-            0 => self.addr(input),
-            1 => self.addi(input),
-            2 => self.mulr(input),
-            3 => self.muli(input),
-            4 => self.banr(input),
-            5 => self.bani(input),
-            6 => self.borr(input),
-            7 => self.bori(input),
-            8 => self.setr(input),
-            9 => self.seti(input),
-            10 => self.gtir(input),
-            11 => self.gtri(input),
-            12 => self.gtrr(input),
-            13 => self.eqir(input),
-            14 => self.eqri(input),
-            15 => self.eqrr(input),
-            _ => Err(Error::Functioncode),
-        }
-    }
-
-    fn addr(&self, input: State) -> Result<State, Error> {
-        self.validate_reg_a()?;
-        self.validate_reg_b()?;
-        self.validate_reg_c()?;
-
-        let mut state = input;
-        state[self.output_c] = state[self.input_a] + state[self.input_b];
-        Ok(state)
-    }
-
-    fn addi(&self, input: State) -> Result<State, Error> {
-        self.validate_reg_a()?;
-        self.validate_reg_c()?;
-
-        let mut state = input;
-        state[self.output_c] = state[self.input_a] + self.input_b;
-        Ok(state)
-    }
-
-    fn mulr(&self, input: State) -> Result<State, Error> {
-        self.validate_reg_a()?;
-        self.validate_reg_b()?;
-        self.validate_reg_c()?;
-
-        let mut state = input;
-        state[self.output_c] = state[self.input_a] * state[self.input_b];
-        Ok(state)
-    }
-
-    fn muli(&self, input: State) -> Result<State, Error> {
-        self.validate_reg_a()?;
-        self.validate_reg_c()?;
-
-        let mut state = input;
-        state[self.output_c] = state[self.input_a] * self.input_b;
-        Ok(state)
-    }
-
-    fn banr(&self, input: State) -> Result<State, Error> {
-        self.validate_reg_a()?;
-        self.validate_reg_b()?;
-        self.validate_reg_c()?;
-
-        let mut state = input;
-        state[self.output_c] = state[self.input_a] & state[self.input_b];
-        Ok(state)
-    }
-
-    fn bani(&self, input: State) -> Result<State, Error> {
-        self.validate_reg_a()?;
-        self.validate_reg_c()?;
-
-        let mut state = input;
-        state[self.output_c] = state[self.input_a] & self.input_b;
-        Ok(state)
-    }
-
-    fn borr(&self, input: State) -> Result<State, Error> {
-        self.validate_reg_a()?;
-        self.validate_reg_b()?;
-        self.validate_reg_c()?;
-
-        let mut state = input;
-        state[self.output_c] = state[self.input_a] | state[self.input_b];
-        Ok(state)
-    }
-
-    fn bori(&self, input: State) -> Result<State, Error> {
-        self.validate_reg_a()?;
-        self.validate_reg_c()?;
-
-        let mut state = input;
-        state[self.output_c] = state[self.input_a] | self.input_b;
-        Ok(state)
-    }
-
-    fn setr(&self, input: State) -> Result<State, Error> {
-        self.validate_reg_a()?;
-        self.validate_reg_c()?;
-
-        let mut state = input;
-        state[self.output_c] = state[self.input_a];
-        Ok(state)
-    }
-
-    fn seti(&self, input: State) -> Result<State, Error> {
-        self.validate_reg_a()?;
-        self.validate_reg_c()?;
-
-        let mut state = input;
-        state[self.output_c] = self.input_a;
-        Ok(state)
-    }
-
-    fn gtir(&self, input: State) -> Result<State, Error> {
-        self.validate_reg_b()?;
-        self.validate_reg_c()?;
-
-        let mut state = input;
-        if self.input_a > state[self.input_b] {
-            state[self.output_c] = 1;
-        } else {
-            state[self.output_c] = 0;
-        }
-        Ok(state)
-    }
-
-    fn gtri(&self, input: State) -> Result<State, Error> {
-        self.validate_reg_a()?;
-        self.validate_reg_c()?;
-
-        let mut state = input;
-        if state[self.input_a] > self.input_b {
-            state[self.output_c] = 1;
-        } else {
-            state[self.output_c] = 0;
-        }
-        Ok(state)
-    }
-
-    fn gtrr(&self, input: State) -> Result<State, Error> {
-        self.validate_reg_a()?;
-        self.validate_reg_b()?;
-        self.validate_reg_c()?;
-
-        let mut state = input;
-        if state[self.input_a] > state[self.input_b] {
-            state[self.output_c] = 1;
-        } else {
-            state[self.output_c] = 0;
-        }
-        Ok(state)
-    }
-
-    fn eqir(&self, input: State) -> Result<State, Error> {
-        self.validate_reg_b()?;
-        self.validate_reg_c()?;
-
-        let mut state = input;
-        if self.input_a == state[self.input_b] {
-            state[self.output_c] = 1;
-        } else {
-            state[self.output_c] = 0;
-        }
-        Ok(state)
-    }
-
-    fn eqri(&self, input: State) -> Result<State, Error> {
-        self.validate_reg_a()?;
-        self.validate_reg_c()?;
-
-        let mut state = input;
-        if state[self.input_a] == self.input_b {
-            state[self.output_c] = 1;
-        } else {
-            state[self.output_c] = 0;
-        }
-        Ok(state)
-    }
-
-    fn eqrr(&self, input: State) -> Result<State, Error> {
-        self.validate_reg_a()?;
-        self.validate_reg_b()?;
-        self.validate_reg_c()?;
-
-        let mut state = input;
-        if state[self.input_a] == state[self.input_b] {
-            state[self.output_c] = 1;
-        } else {
-            state[self.output_c] = 0;
-        }
-        Ok(state)
-    }
-}
+type State = elfcode::State<u32, 4>;
+type Instruction = elfcode::Instruction<u32>;
 
 struct Sample {
     before: State,
@@ -401,7 +87,7 @@ impl Sample {
     fn parser(input: &str) -> IResult<&str, Self> {
         let (input, (before, op, after)) = tuple((
             preceded(pair(multispace0, tag("Before:")), State::parser),
-            Instruction::parser,
+            Instruction::parser_numeric,
             preceded(pair(multispace0, tag("After:")), State::parser),
         ))(input)?;
 
@@ -411,7 +97,7 @@ impl Sample {
     fn find_possible_opcodes(&self) -> Vec<u8> {
         // Returns a vec of all possible function codes
         (0..16)
-            .map(|i| (i, self.op.dispatch_as(self.before.clone(), i)))
+            .map(|i| (i, self.op.dispatch_as(self.before, i)))
             .filter(|(_i, result)| match result {
                 Ok(x) => x == &self.after,
                 _ => false,
@@ -444,8 +130,8 @@ mod test {
 
     #[test]
     fn test_dispatch_as() {
-        let instr = Instruction::from_string("9 2 1 2");
-        let before = State([3, 2, 1, 1]);
+        let (_, instr) = Instruction::parser_numeric("9 2 1 2").unwrap();
+        let before = elfcode::State([3, 2, 1, 1]);
         let after = instr.dispatch_as(before, 2).unwrap();
         assert_eq!(after.0, [3, 2, 2, 1]);
     }
@@ -145,8 +145,14 @@
     How many tiles can the water reach within the range of y values in your scan?
 */
 
+use crate::common::play_if_requested;
+use crate::common::record_if_requested;
 use crate::common::Cardinal;
+use crate::common::Grid;
+use crate::common::Palette;
 use crate::common::Point;
+use crate::common::Simulation;
+use crate::common::ToChar;
 use nom::{
     branch::alt,
     bytes::complete::tag,
@@ -156,7 +162,6 @@ use nom::{
     sequence::{delimited, pair, preceded, tuple},
     IResult,
 };
-use std::collections::HashMap;
 use std::fmt;
 
 #[derive(Clone, Copy, Eq, PartialEq)]
@@ -168,7 +173,7 @@ enum Tile {
     Spring,
 }
 
-impl Tile {
+impl ToChar for Tile {
     fn to_char(&self) -> char {
         match *self {
             Self::Sand => '.',
@@ -180,30 +185,44 @@ impl Tile {
     }
 }
 
+fn palette() -> Palette {
+    Palette::new([0, 0, 0])
+        .with(Tile::Sand.to_char(), [230, 210, 160])
+        .with(Tile::Clay.to_char(), [140, 80, 40])
+        .with(Tile::Water.to_char(), [30, 90, 220])
+        .with(Tile::DriedSand.to_char(), [120, 190, 240])
+        .with(Tile::Spring.to_char(), [255, 255, 255])
+}
+
+#[derive(Clone)]
 struct GeologicMap {
-    tiles: HashMap<Point, Tile>,
+    tiles: Grid<Tile>,
     x_range: (i32, i32),
     y_range: (i32, i32),
     spring: Point,
+    sources: Vec<Point>,  // Where water is still to flow from in this pass
+    snapshot: Grid<Tile>, // Tiles at the start of this pass
 }
 
 impl GeologicMap {
     fn from_string(input: &str) -> Self {
         let mut tiles = Self::parser(input).unwrap().1;
-        let range = Point::get_range(tiles.keys()).unwrap(); // Must not include the spring so we do this first
+        let (min, max) = tiles.bounds().unwrap(); // Must not include the spring so we do this first
 
         let spring = Point { x: 500, y: 0 };
         tiles.insert(spring, Tile::Spring);
 
         Self {
             tiles,
-            x_range: range.0,
-            y_range: range.1,
+            x_range: (min.x, max.x),
+            y_range: (min.y, max.y),
             spring,
+            sources: Vec::new(),
+            snapshot: Grid::sparse(),
         }
     }
 
-    fn parser(input: &str) -> IResult<&str, HashMap<Point, Tile>> {
+    fn parser(input: &str) -> IResult<&str, Grid<Tile>> {
         let (input, values) = many1(alt((
             tuple((
                 success(true),
@@ -225,7 +244,7 @@ impl GeologicMap {
             )),
         )))(input)?;
 
-        let mut tiles = HashMap::new();
+        let mut tiles = Grid::sparse();
         for (x_first, a, b0, b1) in values {
             if x_first == true {
                 let x = a;
@@ -249,7 +268,7 @@ impl GeologicMap {
     }
 
     fn is_blocked(&self, point: &Point) -> bool {
-        match self.tiles.get(point) {
+        match self.tiles.get(*point) {
             Some(Tile::Sand) | Some(Tile::DriedSand) | None => false,
             Some(Tile::Clay) | Some(Tile::Water) => true,
             Some(Tile::Spring) => panic!("Tried to flow into a spring"),
@@ -336,25 +355,8 @@ impl GeologicMap {
     }
 
     fn water_flow(&mut self) {
-        let mut sources: Vec<Point> = vec![self.spring];
-        let mut snapshot = self.to_string();
-        loop {
-            for source in sources.drain(..).collect::<Vec<Point>>() {
-                sources.extend(self.source_flow(&source));
-            }
-            sources.sort(); // This is on balance faster than using a HashSet or BTreeSet to perform deduplication
-            sources.dedup();
-
-            if sources.is_empty() == true {
-                if snapshot == self.to_string() {
-                    // No change during this iteration, we're done
-                    break;
-                } else {
-                    // Something changed, so continue simulating
-                    snapshot = self.to_string();
-                    sources.push(self.spring);
-                }
-            }
+        while self.is_finished() == false {
+            self.tick();
         }
     }
 
@@ -366,27 +368,43 @@ impl GeologicMap {
     }
 }
 
+impl Simulation for GeologicMap {
+    fn tick(&mut self) {
+        if self.sources.is_empty() == true {
+            // Start a new pass from the spring
+            self.snapshot = self.tiles.clone();
+            self.sources.push(self.spring);
+        }
+
+        for source in self.sources.drain(..).collect::<Vec<Point>>() {
+            let new_sources = self.source_flow(&source);
+            self.sources.extend(new_sources);
+        }
+        self.sources.sort(); // This is on balance faster than using a HashSet or BTreeSet to perform deduplication
+        self.sources.dedup();
+    }
+
+    fn is_finished(&self) -> bool {
+        // Done when a whole pass changed nothing
+        self.sources.is_empty() == true && self.tiles == self.snapshot
+    }
+}
+
 impl fmt::Display for GeologicMap {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let (x_range, y_range) = Point::get_range(self.tiles.keys()).unwrap(); // Don't use self range because it won't include any water that flowed out of bounds left or right
-        for y in y_range.0..=y_range.1 {
-            //write!(f, "{:4} ", y)?;
-            for x in x_range.0..=x_range.1 {
-                if let Some(tile) = self.tiles.get(&Point { x, y }) {
-                    write!(f, "{}", tile.to_char())?;
-                } else {
-                    write!(f, ".")?;
-                }
-            }
-            writeln!(f)?;
-        }
-        Ok(())
+        // Don't use self range because it won't include any water that flowed out of bounds left or right
+        let output = self
+            .tiles
+            .render(|_p, tile| tile.map_or('.', |t| t.to_char()));
+        write!(f, "{}", output)
     }
 }
 
 #[aoc(day17, part2)]
 pub fn solve(input: &str) -> u32 {
     let mut geo_map = GeologicMap::from_string(input);
+    play_if_requested(&geo_map);
+    record_if_requested(&geo_map, &palette());
     geo_map.water_flow();
     //println!("{}", geo_map);
     let water_tiles = geo_map.count_water_tiles();
@@ -153,27 +153,37 @@
     What will the total resource value of the lumber collection area be after 10 minutes?
 */
 
-use crate::common::Point;
-use std::collections::HashMap;
+use crate::common::play_if_requested;
+use crate::common::record_if_requested;
+use crate::common::Automaton;
+use crate::common::Boundary;
+use crate::common::FromChar;
+use crate::common::Grid;
+use crate::common::Neighbourhood;
+use crate::common::Palette;
+use crate::common::Simulation;
+use crate::common::ToChar;
 use std::fmt;
 
-#[derive(Clone, Copy, Eq, PartialEq)]
+#[derive(Clone, Copy, Eq, Hash, PartialEq)]
 enum Tile {
     OpenGround,
     Trees,
     Lumberyard,
 }
 
-impl Tile {
-    fn from_char(c: char) -> Self {
+impl FromChar for Tile {
+    fn from_char(c: char) -> Option<Self> {
         match c {
-            '.' => Self::OpenGround,
-            '|' => Self::Trees,
-            '#' => Self::Lumberyard,
-            _ => panic!("Unknown tile: {}", c),
+            '.' => Some(Self::OpenGround),
+            '|' => Some(Self::Trees),
+            '#' => Some(Self::Lumberyard),
+            _ => None,
         }
     }
+}
 
+impl ToChar for Tile {
     fn to_char(&self) -> char {
         match *self {
             Self::OpenGround => '.',
@@ -183,118 +193,87 @@ impl Tile {
     }
 }
 
+fn palette() -> Palette {
+    Palette::new([0, 0, 0])
+        .with(Tile::OpenGround.to_char(), [210, 200, 150])
+        .with(Tile::Trees.to_char(), [40, 140, 50])
+        .with(Tile::Lumberyard.to_char(), [130, 80, 40])
+}
+
+fn next_tile(tile: Tile, neighbours: &Neighbourhood<Tile>) -> Tile {
+    let trees_count = neighbours.count(Tile::Trees);
+    let lumberyard_count = neighbours.count(Tile::Lumberyard);
+    match tile {
+        Tile::OpenGround if trees_count >= 3 => Tile::Trees,
+        Tile::Trees if lumberyard_count >= 3 => Tile::Lumberyard,
+        Tile::Lumberyard if lumberyard_count == 0 || trees_count == 0 => Tile::OpenGround,
+        _ => tile,
+    }
+}
+
+type Rule = fn(Tile, &Neighbourhood<Tile>) -> Tile;
+
+const MINUTES: u32 = 10;
+
+#[derive(Clone)]
 struct Construction {
-    tiles: HashMap<Point, Tile>,
+    area: Automaton<Tile, Rule>,
     size: usize,
+    minute: u32,
 }
 
 impl Construction {
     fn from_string(input: &str) -> Self {
-        let mut tiles = HashMap::new();
-        let mut p = Point::new();
-        for line in input.trim().lines() {
-            for c in line.chars() {
-                tiles.insert(p, Tile::from_char(c));
-                p.x += 1;
-            }
-            p.x = 0;
-            p.y += 1;
-        }
-
-        let range = Point::get_range(tiles.keys()).unwrap();
-        let size = (range.0).1 - (range.0).0 + 1;
-        assert_eq!(size, (range.1).1 - (range.1).0 + 1);
+        let tiles = Grid::from_string(input);
+        let (min, max) = tiles.bounds().unwrap();
+        let size = max.x - min.x + 1;
+        assert_eq!(size, max.y - min.y + 1);
 
+        // Acres past the edge don't count
         Self {
-            tiles,
+            area: Automaton::new(tiles, Boundary::Fixed, next_tile),
             size: size as usize,
+            minute: 0,
         }
     }
 
-    fn determine_next_tile(&self, point: &Point) -> Option<Tile> {
-        let adjacents = point.adjacents();
-        let trees_count = adjacents
-            .iter()
-            .map(|adj| self.tiles.get(adj))
-            .filter(|&t| t == Some(&Tile::Trees))
-            .count();
-        let lumberyard_count = adjacents
-            .iter()
-            .map(|adj| self.tiles.get(adj))
-            .filter(|&t| t == Some(&Tile::Lumberyard))
-            .count();
-        match self.tiles.get(&point) {
-            Some(Tile::OpenGround) => {
-                if trees_count >= 3 {
-                    return Some(Tile::Trees);
-                }
-            }
-            Some(Tile::Trees) => {
-                if lumberyard_count >= 3 {
-                    return Some(Tile::Lumberyard);
-                }
-            }
-            Some(Tile::Lumberyard) => {
-                if lumberyard_count == 0 || trees_count == 0 {
-                    return Some(Tile::OpenGround);
-                }
-            }
-            None => panic!("Unexpected empty tile at {}", point),
-        }
-
-        None
-    }
-
     fn sim(&mut self, minutes: u32) {
-        let mut new_tiles: HashMap<Point, Tile> = HashMap::new();
         for _ in 0..minutes {
-            new_tiles.clear();
-            for &point in self.tiles.keys() {
-                if let Some(t) = self.determine_next_tile(&point) {
-                    new_tiles.insert(point, t);
-                }
-            }
-            self.tiles.extend(&new_tiles);
+            self.tick();
         }
     }
 
     fn resource_value(&self) -> u32 {
-        let trees_count = self
-            .tiles
-            .values()
-            .filter(|&tile| tile == &Tile::Trees)
-            .count() as u32;
-        let lumberyard_count = self
-            .tiles
-            .values()
-            .filter(|&tile| tile == &Tile::Lumberyard)
-            .count() as u32;
+        let trees_count = self.area.count(Tile::Trees) as u32;
+        let lumberyard_count = self.area.count(Tile::Lumberyard) as u32;
         trees_count * lumberyard_count
     }
 }
 
+impl Simulation for Construction {
+    fn tick(&mut self) {
+        self.area.step();
+        self.minute += 1;
+    }
+
+    // Only the first ten minutes matter, so playback and recording stop there
+    fn is_finished(&self) -> bool {
+        self.minute >= MINUTES
+    }
+}
+
 impl fmt::Display for Construction {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let range = Point::get_range(self.tiles.keys()).unwrap();
-        for y in (range.1).0..=(range.1).1 {
-            for x in (range.0).0..=(range.0).1 {
-                if let Some(tile) = self.tiles.get(&Point { x, y }) {
-                    write!(f, "{}", tile.to_char())?;
-                } else {
-                    write!(f, " ")?;
-                }
-            }
-            writeln!(f)?;
-        }
-
-        Ok(())
+        write!(f, "{}", self.area.cells())
     }
 }
 
 #[aoc(day18, part1)]
 pub fn solve(input: &str) -> u32 {
     let mut construction = Construction::from_string(input);
-    construction.sim(10);
+    play_if_requested(&construction);
+    record_if_requested(&construction, &palette());
+    construction.sim(MINUTES);
     //println!("{}", construction);
 
     let resource_value = construction.resource_value();
@@ -463,5 +442,6 @@ mod test {
         assert_eq!(construction.to_string().trim(), result.trim());
 
         assert_eq!(construction.resource_value(), 1147);
+        assert!(construction.is_finished());
     }
 }
@@ -0,0 +1,46 @@
+; The day 19 input, patched so the inner loop ends as soon as B * C passes D
+#ip 5
+        seti init-1 0 ip        ; Jump to init
+outer:  seti 1 0 B              ; for (B = 1; B <= D; B++)
+inner:  seti 1 0 C              ;   for (C = 1; C <= D; C++)
+test:   mulr B C E
+        eqrr E D E
+        addr E ip ip            ;     if (B * C == D)
+        addi ip 2 ip
+        addr B A A              ;       A += B
+        setr D 0 C              ;       C = D
+        seti check-1 0 ip       ;     Jump to the new check at the end of the program
+next_c: addi C 1 C
+        gtrr C D E
+        addr ip E ip
+        seti test-1 0 ip
+        addi B 1 B
+        gtrr B D E
+        addr E ip ip
+        seti inner-1 0 ip
+        mulr ip ip ip           ; Exit
+
+; Calculates D. The IP is used as a constant here, so these lines must not move.
+init:   addi D 76 D
+        muli D 11 D
+        addi E 8 E
+        mulr E ip E
+        addi E 13 E
+        addr D E D
+        addr ip A ip            ; Part 2 makes D much bigger
+        seti outer-1 0 ip
+        setr ip 0 E
+        mulr E ip E
+        addr ip E E
+        mulr ip E E
+        muli E 14 E
+        mulr E ip E
+        addr D E D
+        seti 0 0 A
+        seti outer-1 0 ip
+
+check:  mulr B C E              ;     if (B * C >= D)
+        gtrr D E E
+        addr ip E ip
+        setr D 0 C              ;       C = D
+        seti next_c-1 0 ip
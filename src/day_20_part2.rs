@@ -5,9 +5,10 @@
     How many rooms have a shortest path from your current location that pass through at least 1000 doors?
 */
 
+use crate::common::bfs;
 use crate::common::Cardinal;
+use crate::common::Grid;
 use crate::common::Point;
-use std::collections::HashMap;
 use std::fmt;
 
 struct Room {
@@ -52,13 +53,13 @@ struct Frame {
 }
 
 struct Map {
-    rooms: HashMap<Point, Room>,
+    rooms: Grid<Room>,
 }
 
 impl Map {
     fn from_string(regex: &str) -> Self {
         let mut map = Self {
-            rooms: HashMap::new(),
+            rooms: Grid::sparse(),
         };
         map.find_routes(regex);
         map
@@ -127,66 +128,44 @@ impl Map {
     }
 
     fn add_room(&mut self, point: Point, from_dir: Cardinal) {
-        let room = self.rooms.entry(point).or_insert_with(Room::new);
-        room.set_door(from_dir, true);
+        self.rooms
+            .get_or_insert_with(point, Room::new)
+            .set_door(from_dir, true);
     }
 
-    fn count_far_rooms(&self) -> usize {
-        let mut visited_steps: HashMap<Point, u32> = HashMap::new(); // Track which rooms were visited, indicating how many steps to each one
-        let mut frontier: Vec<Point> = vec![Point::new()];
-
-        let mut steps = 0;
-        while frontier.is_empty() == false {
-            for location in frontier.drain(..).collect::<Vec<Point>>() {
-                visited_steps.insert(location, steps);
-
-                let room = self
-                    .rooms
-                    .get(&location)
-                    .expect("Visited room does not exist!");
-                if room.door_north == true {
-                    let next_location = location.step(Cardinal::North, 1);
-                    if visited_steps.get(&next_location) == None {
-                        frontier.push(next_location);
-                    }
-                }
-                if room.door_south == true {
-                    let next_location = location.step(Cardinal::South, 1);
-                    if visited_steps.get(&next_location) == None {
-                        frontier.push(next_location);
-                    }
-                }
-                if room.door_east == true {
-                    let next_location = location.step(Cardinal::East, 1);
-                    if visited_steps.get(&next_location) == None {
-                        frontier.push(next_location);
-                    }
-                }
-                if room.door_west == true {
-                    let next_location = location.step(Cardinal::West, 1);
-                    if visited_steps.get(&next_location) == None {
-                        frontier.push(next_location);
-                    }
-                }
-            }
-
-            // Remove any duplicates
-            frontier.sort_unstable();
-            frontier.dedup();
-
-            steps += 1;
-        }
+    fn rooms_through_doors(&self, location: &Point) -> Vec<Point> {
+        let room = self
+            .rooms
+            .get(*location)
+            .expect("Visited room does not exist!");
+        [
+            Cardinal::North,
+            Cardinal::South,
+            Cardinal::East,
+            Cardinal::West,
+        ]
+        .iter()
+        .filter(|&&dir| room.has_door(dir) == true)
+        .map(|&dir| location.step(dir, 1))
+        .collect()
+    }
 
-        // Find the room with the furthest distance
-        visited_steps.values().filter(|&&d| d >= 1000).count()
+    fn count_far_rooms(&self) -> usize {
+        // Count the rooms at least 1000 doors away
+        let search = bfs(
+            Point::new(),
+            |location| self.rooms_through_doors(location),
+            |_| false,
+        );
+        search.costs().values().filter(|&&d| d >= 1000).count()
     }
 }
 
 impl fmt::Display for Map {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         // First, create a map of the pixels for each room (one room consists of a 3x3 grid of pixels)
-        let mut pixels: HashMap<Point, char> = HashMap::new();
-        for (p, r) in &self.rooms {
+        let mut pixels: Grid<char> = Grid::sparse();
+        for (p, r) in self.rooms.iter() {
             let draw_p = Point {
                 x: p.x * 2,
                 y: p.y * 2,
@@ -237,23 +216,12 @@ impl fmt::Display for Map {
         }
 
         // Now write the entire pixel map
-        let x_min = pixels.keys().map(|p| p.x).min().unwrap();
-        let x_max = pixels.keys().map(|p| p.x).max().unwrap();
-        let y_min = pixels.keys().map(|p| p.y).min().unwrap();
-        let y_max = pixels.keys().map(|p| p.y).max().unwrap();
         writeln!(f)?;
-        for y in y_min..=y_max {
-            for x in x_min..=x_max {
-                if let Some(pixel) = pixels.get(&Point { x, y }) {
-                    write!(f, "{}", pixel)?;
-                } else {
-                    write!(f, " ")?;
-                }
-            }
-            writeln!(f)?;
-        }
-
-        Ok(())
+        write!(
+            f,
+            "{}",
+            pixels.render(|_p, pixel| pixel.copied().unwrap_or(' '))
+        )
     }
 }
 
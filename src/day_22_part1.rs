@@ -55,7 +55,10 @@
 */
 
 use crate::common::modulo;
+use crate::common::save_if_requested;
+use crate::common::Palette;
 use crate::common::Point;
+use crate::common::ToChar;
 use std::collections::HashMap;
 use std::fmt;
 
@@ -76,6 +79,16 @@ impl RegionType {
         }
     }
 
+    fn to_risk_level(self) -> u32 {
+        match self {
+            Self::Rocky => 0,
+            Self::Wet => 1,
+            Self::Narrow => 2,
+        }
+    }
+}
+
+impl ToChar for RegionType {
     fn to_char(&self) -> char {
         match self {
             Self::Rocky => '.',
@@ -83,14 +96,16 @@ impl RegionType {
             Self::Narrow => '|',
         }
     }
+}
 
-    fn to_risk_level(&self) -> u32 {
-        match self {
-            Self::Rocky => 0,
-            Self::Wet => 1,
-            Self::Narrow => 2,
-        }
-    }
+fn palette() -> Palette {
+    // The mouth and target are drawn as M and T
+    Palette::new([0, 0, 0])
+        .with(RegionType::Rocky.to_char(), [150, 140, 130])
+        .with(RegionType::Wet.to_char(), [50, 110, 200])
+        .with(RegionType::Narrow.to_char(), [110, 70, 40])
+        .with('M', [240, 60, 40])
+        .with('T', [240, 60, 40])
 }
 
 struct Cave {
@@ -235,6 +250,7 @@ impl fmt::Display for Cave {
 pub fn solve(input: &str) -> u32 {
     let mut cave = Cave::from_string(input);
     let risk_level = cave.risk_level();
+    save_if_requested(&cave, &palette());
     println!("Risk level: {}", risk_level);
     assert_eq!(risk_level, 6256);
     risk_level
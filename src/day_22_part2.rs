@@ -275,9 +275,10 @@
     What is the fewest number of minutes you can take to reach the target?
 */
 
+use crate::common::astar;
 use crate::common::modulo;
 use crate::common::Point;
-use std::cmp::Ordering;
+use crate::common::ToChar;
 use std::collections::HashMap;
 use std::fmt;
 
@@ -297,7 +298,9 @@ impl RegionType {
             x => unreachable!("Impossible erosion level: {}", x),
         }
     }
+}
 
+impl ToChar for RegionType {
     fn to_char(&self) -> char {
         match self {
             Self::Rocky => '.',
@@ -444,94 +447,51 @@ impl Cave {
         }
     }
 
-    fn find_fastest_time_to_target(&mut self) -> u32 {
-        let mut minutes: HashMap<Node, u32> = HashMap::new();
+    fn next_nodes(&mut self, node: &Node) -> Vec<Node> {
+        // First movements in each direction, keeping the current tool
+        let mut next_nodes: Vec<Node> = node
+            .location
+            .orthogonals()
+            .into_iter()
+            .filter(|next| next.x >= 0 && next.y >= 0)
+            .filter(|&next| node.tool.is_compatible(self.region_type(next)) == true)
+            .map(|location| Node {
+                location,
+                tool: node.tool,
+            })
+            .collect();
+
+        // Then a tool change
+        next_nodes.push(Node {
+            location: node.location,
+            tool: node.tool.switch(self.region_type(node.location)),
+        });
+        next_nodes
+    }
 
+    fn find_fastest_time_to_target(&mut self) -> u32 {
         let start = Node {
             location: Point::new(),
             tool: Tool::Torch,
         };
-        let mut frontier: Vec<(Node, u32)> = vec![(start, 0)];
-
-        while frontier.is_empty() == false {
-            let (curr_node, curr_time) = frontier.pop().unwrap();
-
-            // Check if the target was reached
-            let target = Node {
-                location: self.target,
-                tool: Tool::Torch,
-            };
-            if curr_node == target {
-                // Target reached, return the time it took to reach
-                return curr_time;
-            }
-
-            // First add movements in each direction
-            for next in curr_node.location.orthogonals() {
-                if next.x < 0 || next.y < 0 {
-                    // Out of bounds
-                    continue;
-                }
-
-                if curr_node.tool.is_compatible(self.region_type(next)) == false {
-                    // Incompatible with the current tool
-                    continue;
-                }
-
-                let next_node = Node {
-                    location: next,
-                    tool: curr_node.tool,
-                };
-                let next_time = curr_time + 1;
-
-                // Record (or overwrite) the best time for the next node if it is better than what has been seen before
-                if let Some(best_time) = minutes.get_mut(&next_node) {
-                    if next_time < *best_time {
-                        // Node has already been seen and we have a better time
-                        *best_time = next_time;
-                    } else {
-                        // Already reached this node from a quicker path, ignore it
-                        continue;
-                    }
-                } else {
-                    minutes.insert(next_node, next_time);
-                }
-
-                // Add to the frontier
-                frontier.push((next_node, next_time));
-            }
-
-            // Then add tool change
-            let next_tool = curr_node.tool.switch(self.region_type(curr_node.location));
-            let next_node = Node {
-                location: curr_node.location,
-                tool: next_tool,
-            };
-            let next_time = curr_time + 7;
-
-            // Record (or overwrite) the best time for the next node if it is better than what has been seen before
-            if let Some(best_time) = minutes.get_mut(&next_node) {
-                if next_time < *best_time {
-                    // Node has already been seen and we have a better time - shouldn't happen in practice
-                    *best_time = next_time;
-                    frontier.push((next_node, next_time));
-                } else {
-                    // Already reached this node from a quicker path, ignore it
-                }
-            } else {
-                minutes.insert(next_node, next_time);
-                frontier.push((next_node, next_time));
-            }
-
-            // Sort the frontier so that the next node checked is also the shortest path to that node. Remove any duplicates.
-            frontier.sort_unstable_by(|a, b| match b.1.cmp(&a.1) {
-                Ordering::Equal => b.0.cmp(&a.0),
-                ord => ord,
-            });
-            frontier.dedup();
-        }
+        let target = Node {
+            location: self.target,
+            tool: Tool::Torch,
+        };
 
-        panic!("Failed to find target!");
+        // Moving takes a minute and switching tools takes seven. At best every remaining step is
+        // a move, plus a switch if not already holding the torch.
+        let search = astar(
+            start,
+            |node| self.next_nodes(node),
+            |from, to| if from.tool == to.tool { 1 } else { 7 },
+            |node| {
+                let switch = if node.tool == target.tool { 0 } else { 7 };
+                Point::manhattan(node.location, target.location) + switch
+            },
+            |&node| node == target,
+        );
+        search.cost(target).expect("Failed to find target!")
     }
 }
 